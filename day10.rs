@@ -1,9 +1,8 @@
-use std::cmp::Ordering;
+use std::cmp::{Ordering, Reverse};
 use std::collections::HashMap;
 use std::env;
 use std::fmt;
 use std::fs::File;
-use std::hash::{Hash, Hasher};
 use std::io::{BufRead, BufReader};
 
 fn gcd(x: i32, y: i32) -> i32 {
@@ -17,7 +16,7 @@ fn gcd(x: i32, y: i32) -> i32 {
     x
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct Asteroid {
     x: i32,
     y: i32,
@@ -27,9 +26,6 @@ impl Asteroid {
     fn new(x: i32, y: i32) -> Asteroid {
         Asteroid { x, y }
     }
-    fn distance(&self, other: &Self) -> f64 {
-        ((other.x - self.x).pow(2) as f64 + (other.y - self.y).pow(2) as f64).sqrt()
-    }
     fn value(&self) -> i32 {
         100 * self.x + self.y
     }
@@ -41,167 +37,378 @@ impl fmt::Display for Asteroid {
     }
 }
 
-#[derive(Debug, Eq)]
-struct Fraction {
-    num: i32,
-    den: i32,
+// A line of sight: the step between grid points along it, reduced by the gcd
+// of its coordinates. As in the input, y grows downwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Direction {
+    dx: i32,
+    dy: i32,
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
-enum Quadrant {
-    First,
-    Second,
-    Third,
-    Fourth,
+impl Direction {
+    // The direction from `a` to `b`, and how many of its steps apart they are.
+    fn between(a: &Asteroid, b: &Asteroid) -> (Direction, i32) {
+        let (dx, dy) = (b.x - a.x, b.y - a.y);
+        let steps = gcd(dx, dy);
+        if steps == 0 {
+            panic!("{} == {}", a, b);
+        }
+        (
+            Direction {
+                dx: dx / steps,
+                dy: dy / steps,
+            },
+            steps,
+        )
+    }
+    // 0 from straight up to just before straight down, turning clockwise,
+    // and 1 for the rest of the turn.
+    fn half(&self) -> u8 {
+        if self.dx > 0 || (self.dx == 0 && self.dy < 0) {
+            0
+        } else {
+            1
+        }
+    }
 }
 
-impl fmt::Display for Quadrant {
+impl fmt::Display for Direction {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let s = match *self {
-            Quadrant::First => "1",
-            Quadrant::Second => "2",
-            Quadrant::Third => "3",
-            Quadrant::Fourth => "4",
-        };
-        write!(f, "{}", s)
+        write!(f, "<{}, {}>", self.dx, self.dy)
     }
 }
 
-impl Fraction {
-    fn new(num: i32, den: i32) -> Self {
-        let gcd = gcd(num, den);
-        Fraction {
-            num: num / gcd,
-            den: den / gcd,
-        }
-    }
-    fn quadrant(&self) -> Quadrant {
-        match (self.num.cmp(&0), self.den.cmp(&0)) {
-            (Ordering::Greater, Ordering::Greater) => Quadrant::First,
-            (Ordering::Equal, Ordering::Greater) => Quadrant::First,
-            (Ordering::Less, Ordering::Greater) => Quadrant::Second,
-            (Ordering::Less, Ordering::Less) => Quadrant::Third,
-            (Ordering::Equal, Ordering::Less) => Quadrant::Third,
-            (Ordering::Greater, Ordering::Less) => Quadrant::Fourth,
-            (_, Ordering::Equal) => panic!("{} equals infinity!", self),
-        }
+// Clockwise from straight up, the way the laser turns.
+impl Ord for Direction {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.half().cmp(&other.half()).then_with(|| {
+            // within half a turn, `other` comes later when it is clockwise
+            // of `self`, which is when the cross product is positive
+            let cross = self.dx as i64 * other.dy as i64 - self.dy as i64 * other.dx as i64;
+            0.cmp(&cross)
+        })
     }
 }
 
-impl Hash for Fraction {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        (self.num.cmp(&0)).hash(state);
-        (self.den.cmp(&0)).hash(state);
-        self.num.abs().hash(state);
-        self.den.abs().hash(state);
+impl PartialOrd for Direction {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
-impl PartialEq for Fraction {
-    fn eq(&self, other: &Self) -> bool {
-        self.num.cmp(&0) == other.num.cmp(&0)
-            && self.den.cmp(&0) == other.den.cmp(&0)
-            && self.num.abs() == other.num.abs()
-            && self.den.abs() == other.den.abs()
-    }
+struct Field {
+    asteroids: Vec<Asteroid>,
+    width: i32,
+    height: i32,
 }
 
-impl fmt::Display for Fraction {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "({}/{}) [{}]", self.num, self.den, self.quadrant())
+impl Field {
+    fn read(name: &str) -> Field {
+        let f = File::open(name).unwrap();
+        Field::from_reader(BufReader::new(f))
     }
-}
 
-impl Ord for Fraction {
-    fn cmp(&self, other: &Self) -> Ordering {
-        if self.quadrant() != other.quadrant() {
-            self.quadrant().cmp(&other.quadrant())
-        } else {
-            (self.den as i64 * other.num as i64).cmp(&(self.num as i64 * other.den as i64))
+    fn from_reader(reader: impl BufRead) -> Field {
+        let mut asteroids = Vec::new();
+        let mut width = 0;
+        let mut height = 0;
+        for (y, line) in reader.lines().enumerate() {
+            let line = line.unwrap();
+            for (x, c) in line.chars().enumerate() {
+                if c == '#' {
+                    asteroids.push(Asteroid::new(x as i32, y as i32));
+                }
+            }
+            width = width.max(line.len() as i32);
+            height = y as i32 + 1;
+        }
+        Field {
+            asteroids,
+            width,
+            height,
         }
     }
-}
 
-impl PartialOrd for Fraction {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(&other))
+    fn find(&self, x: i32, y: i32) -> Option<usize> {
+        self.asteroids
+            .iter()
+            .position(|a| *a == Asteroid::new(x, y))
     }
-}
 
-fn slope(a: &Asteroid, b: &Asteroid) -> Fraction {
-    let dx = b.x - a.x;
-    let dy = a.y - b.y;
-    if dx == 0 {
-        Fraction::new(
-            match dy.cmp(&0) {
-                Ordering::Less => i32::min_value() + 1,
-                Ordering::Greater => i32::max_value() - 1,
-                _ => panic!("{} == {}", a, b),
-            },
-            1,
-        )
-    } else {
-        Fraction::new(dy, dx)
+    // The other asteroids grouped by the line of sight from `station` they
+    // are on.
+    fn lines_of_sight(&self, station: usize) -> HashMap<Direction, Vec<usize>> {
+        let mut result: HashMap<Direction, Vec<usize>> = HashMap::new();
+        let v = &self.asteroids[station];
+        for (j, u) in self.asteroids.iter().enumerate() {
+            if v != u {
+                result
+                    .entry(Direction::between(v, u).0)
+                    .or_default()
+                    .push(j);
+            }
+        }
+        result
+    }
+
+    fn visible(&self, station: usize) -> usize {
+        self.lines_of_sight(station).len()
+    }
+
+    // The asteroid that sees the most others.
+    fn best(&self) -> Option<usize> {
+        let visible = (0..self.asteroids.len())
+            .map(|i| self.visible(i))
+            .collect::<Vec<_>>();
+        // the first one wins ties
+        (0..visible.len()).rev().max_by_key(|i| visible[*i])
+    }
+
+    // Every other asteroid in the order the laser at `station` vaporizes them.
+    fn vaporization_order(&self, station: usize) -> Vec<usize> {
+        let base = &self.asteroids[station];
+        let mut m = self.lines_of_sight(station);
+        // farthest first, so popping gives the nearest
+        for (_, v) in m.iter_mut() {
+            v.sort_by_key(|a| Reverse(Direction::between(base, &self.asteroids[*a]).1));
+        }
+        let mut v = m.into_iter().collect::<Vec<(Direction, Vec<usize>)>>();
+        v.sort_by_key(|a| a.0);
+
+        let mut result = Vec::new();
+        while result.len() + 1 < self.asteroids.len() {
+            for i in v.iter_mut() {
+                if let Some(a) = i.1.pop() {
+                    result.push(a);
+                }
+            }
+        }
+        result
+    }
+
+    // The `n`th asteroid vaporized, counting from 1.
+    fn vaporized(&self, station: usize, n: usize) -> Option<&Asteroid> {
+        let order = self.vaporization_order(station);
+        order.get(n.checked_sub(1)?).map(|i| &self.asteroids[*i])
+    }
+
+    // Every asteroid with how many others it sees.
+    fn visibility(&self) -> Vec<(Asteroid, usize)> {
+        (0..self.asteroids.len())
+            .map(|i| (self.asteroids[i], self.visible(i)))
+            .collect()
+    }
+
+    // The field with every asteroid showing how many others it sees, scaled
+    // from 0 to 9.
+    fn heatmap(&self, visibility: &[(Asteroid, usize)]) -> String {
+        let max = visibility.iter().map(|v| v.1).max().unwrap_or(0).max(1);
+        let mut grid = vec![vec!['.'; self.width as usize]; self.height as usize];
+        for (a, count) in visibility.iter() {
+            grid[a.y as usize][a.x as usize] =
+                std::char::from_digit((count * 9 / max) as u32, 10).unwrap();
+        }
+        let mut result = grid
+            .iter()
+            .map(|row| row.iter().collect::<String>() + "\n")
+            .collect::<String>();
+        result.push_str(&format!("9 = {} asteroids in sight\n", max));
+        result
     }
 }
 
+// Value of a `--name value` option given anywhere on the command line.
+fn option(name: &str) -> Option<String> {
+    let args = env::args().collect::<Vec<_>>();
+    let i = args.iter().position(|a| a == name)?;
+    args.get(i + 1).cloned()
+}
+
 fn main() {
-    let f = File::open(env::args().nth(1).unwrap()).unwrap();
-    let mut asteroids = Vec::new();
-    let reader = BufReader::new(f);
-    for (y, line) in reader.lines().enumerate() {
-        let line = line.unwrap();
-        for (x, c) in line.chars().enumerate() {
-            if c == '#' {
-                asteroids.push(Asteroid::new(x as i32, y as i32));
-            }
+    let field = Field::read(&env::args().nth(1).unwrap());
+    if env::args().any(|a| a == "--heatmap") {
+        let mut visibility = field.visibility();
+        print!("{}", field.heatmap(&visibility));
+        // the exact counts, most seen first
+        visibility.sort_by_key(|v| Reverse(v.1));
+        for (a, count) in visibility.iter() {
+            println!("{} sees {}", a, count);
         }
     }
 
-    // part 1
-    let mut m: HashMap<Fraction, Vec<usize>> = HashMap::new();
-    let mut index = 0;
-    for (i, v) in asteroids.iter().enumerate() {
-        let mut mi: HashMap<Fraction, Vec<usize>> = HashMap::new();
-        for (j, u) in asteroids.iter().enumerate() {
-            if v != u {
-                let s = slope(v, u);
-                let e = mi.entry(s).or_insert(Vec::new());
-                e.push(j);
+    // part 1, unless the station is given as `--station x,y`
+    let station = match option("--station") {
+        Some(at) => {
+            let (x, y) = at.split_once(',').expect("station should be given as x,y");
+            match field.find(x.trim().parse().unwrap(), y.trim().parse().unwrap()) {
+                Some(station) => station,
+                None => {
+                    println!("there is no asteroid at {}", at);
+                    return;
+                }
             }
         }
-        if m.len() < mi.len() {
-            m = mi;
-            index = i;
+        None => match field.best() {
+            Some(station) => station,
+            None => {
+                println!("there are no asteroids");
+                return;
+            }
+        },
+    };
+    println!("{}: {}", field.asteroids[station], field.visible(station));
+
+    // part 2
+    if env::args().any(|a| a == "--order") {
+        for (n, i) in field.vaporization_order(station).iter().enumerate() {
+            println!("{}: {}", n + 1, field.asteroids[*i]);
         }
     }
-    println!("{}: {}", asteroids[index], m.len());
+    let n = option("--nth").map_or(200, |n| n.parse::<usize>().unwrap());
+    match field.vaporized(station, n) {
+        Some(asteroid) => println!("{}", asteroid.value()),
+        None => println!("fewer than {} asteroids get vaporized", n),
+    }
+}
 
-    // part 2
-    for (_, v) in m.iter_mut() {
-        (*v).sort_by(|a, b| {
-            let da = asteroids[index].distance(&asteroids[*a]);
-            let db = asteroids[index].distance(&asteroids[*b]);
-            db.partial_cmp(&da).unwrap()
-        });
-    }
-    let mut v = m.into_iter().collect::<Vec<(Fraction, Vec<usize>)>>();
-    v.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
-
-    let mut n = 0;
-    loop {
-        for i in v.iter_mut() {
-            if !(*i).1.is_empty() {
-                let a = (*i).1.pop();
-                n += 1;
-                if n == 200 {
-                    println!("{}", asteroids[a.unwrap()].value());
-                    break;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The large example from the puzzle, with some of the asteroids it lists by
+    // when they get vaporized from the best station at (11, 13).
+    const SAMPLE: &str = ".#..##.###...#######
+##.############..##.
+.#.######.########.#
+.###.#######.####.#.
+#####.##.#.##.###.##
+..#####..#.#########
+####################
+#.####....###.#.#.##
+##.#################
+#####.##.###..####..
+..######..##.#######
+####.##.####...##..#
+.#####..#.######.###
+##...#.##########...
+#.##########.#######
+.####.#.###.###.#.##
+....##.##.###..#####
+.#.#.###########.###
+#.#.#.#####.####.###
+###.##.####.##.#..##";
+
+    const VAPORIZED: [(usize, i32, i32); 11] = [
+        (1, 11, 12),
+        (2, 12, 1),
+        (3, 12, 2),
+        (10, 12, 8),
+        (20, 16, 0),
+        (50, 16, 9),
+        (100, 10, 16),
+        (199, 9, 6),
+        (200, 8, 2),
+        (201, 10, 9),
+        (299, 11, 1),
+    ];
+
+    // Clockwise angle from straight up, between 0 and 2 pi.
+    fn angle(d: &Direction) -> f64 {
+        let a = (d.dx as f64).atan2(-d.dy as f64);
+        if a < 0.0 {
+            a + 2.0 * std::f64::consts::PI
+        } else {
+            a
+        }
+    }
+
+    // xorshift, to draw directions without pulling in a crate
+    fn random(state: &mut u64, range: i32) -> i32 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        (*state % (2 * range as u64 + 1)) as i32 - range
+    }
+
+    #[test]
+    fn best_station() {
+        let field = Field::from_reader(SAMPLE.as_bytes());
+        let station = field.best().unwrap();
+        assert_eq!(field.asteroids[station], Asteroid::new(11, 13));
+        assert_eq!(field.visible(station), 210);
+    }
+
+    // the heatmap counts are the real ones, not the scaled digits
+    #[test]
+    fn visibility_counts() {
+        let field = Field::from_reader(SAMPLE.as_bytes());
+        let visibility = field.visibility();
+        assert_eq!(visibility.len(), field.asteroids.len());
+        assert!(visibility.contains(&(Asteroid::new(11, 13), 210)));
+        assert_eq!(visibility.iter().map(|v| v.1).max(), Some(210));
+        let heatmap = field.heatmap(&visibility);
+        assert!(heatmap.ends_with("9 = 210 asteroids in sight\n"));
+    }
+
+    #[test]
+    fn vaporization_order() {
+        let field = Field::from_reader(SAMPLE.as_bytes());
+        let order = field.vaporization_order(field.best().unwrap());
+        for (n, x, y) in VAPORIZED.iter() {
+            let a = &field.asteroids[order[n - 1]];
+            assert_eq!(*a, Asteroid::new(*x, *y), "vaporized {}", n);
+        }
+    }
+
+    // every direction on a small grid sorts the way atan2 does
+    #[test]
+    fn directions_sort_by_angle() {
+        let range = 30;
+        let mut directions = Vec::new();
+        for dx in -range..=range {
+            for dy in -range..=range {
+                if gcd(dx, dy) == 1 {
+                    directions.push(Direction { dx, dy });
                 }
             }
         }
-        if n == 200 {
-            break;
+        directions.sort();
+        for pair in directions.windows(2) {
+            let (a, b) = (&pair[0], &pair[1]);
+            assert!(angle(a) < angle(b), "{} before {}", a, b);
+        }
+    }
+
+    // and so do random pairs from a large one, skipping those too close for
+    // atan2 to tell apart
+    #[test]
+    fn random_pairs_agree_with_atan2() {
+        let mut state = 0x2545_f491_4f6c_dd1d;
+        let origin = Asteroid::new(0, 0);
+        for _ in 0..100_000 {
+            let a = Asteroid::new(random(&mut state, 10_000), random(&mut state, 10_000));
+            // a third of the time a point right next to the line through `a`
+            let b = if random(&mut state, 1) == 0 {
+                let (dx, dy) = (random(&mut state, 1), random(&mut state, 1));
+                Asteroid::new(a.x * 2 + dx, a.y * 2 + dy)
+            } else {
+                Asteroid::new(random(&mut state, 10_000), random(&mut state, 10_000))
+            };
+            if a == origin || b == origin {
+                continue;
+            }
+            let da = Direction::between(&origin, &a).0;
+            let db = Direction::between(&origin, &b).0;
+            let (aa, ab) = (angle(&da), angle(&db));
+            if (aa - ab).abs() < 1e-12 && da != db {
+                continue;
+            }
+            let expected = if da == db {
+                Ordering::Equal
+            } else {
+                aa.partial_cmp(&ab).unwrap()
+            };
+            assert_eq!(da.cmp(&db), expected, "{} vs {}", da, db);
         }
     }
 }
@@ -1,10 +1,11 @@
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::{BufRead, BufReader};
 use std::env;
 use std::cmp::Ordering;
 use regex::Regex;
 use num::Integer;
 use std::fmt;
+use std::rc::Rc;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 struct Vec3 {
@@ -20,6 +21,22 @@ impl Vec3 {
     fn sum(&self) -> i32 {
         self.x.abs() + self.y.abs() + self.z.abs()
     }
+    fn get(&self, axis: usize) -> i32 {
+        match axis {
+            0 => self.x,
+            1 => self.y,
+            2 => self.z,
+            _ => panic!("no axis {}", axis),
+        }
+    }
+    fn get_mut(&mut self, axis: usize) -> &mut i32 {
+        match axis {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            2 => &mut self.z,
+            _ => panic!("no axis {}", axis),
+        }
+    }
 }
 
 impl fmt::Display for Vec3 {
@@ -38,15 +55,22 @@ impl Moon {
     fn new(pos: Vec3, vel: Vec3) -> Self {
         Self { pos, vel, }
     }
-    fn update_velocity(&mut self, other: &Self) {
-        self.vel.x += compare(self.pos.x, other.pos.x);
-        self.vel.y += compare(self.pos.y, other.pos.y);
-        self.vel.z += compare(self.pos.z, other.pos.z);
+    fn update_velocity(&mut self, other: &Self, law: &dyn Law, dims: usize) {
+        let pull = law.pull(&self.pos, &other.pos, dims);
+        for axis in 0..dims {
+            *self.vel.get_mut(axis) += pull.get(axis);
+        }
+    }
+    fn slow_down(&mut self, law: &dyn Law, dims: usize) {
+        let drag = law.drag(&self.vel, dims);
+        for axis in 0..dims {
+            *self.vel.get_mut(axis) += drag.get(axis);
+        }
     }
-    fn walk(&mut self) {
-        self.pos.x += self.vel.x;
-        self.pos.y += self.vel.y;
-        self.pos.z += self.vel.z;
+    fn walk(&mut self, dims: usize) {
+        for axis in 0..dims {
+            *self.pos.get_mut(axis) += self.vel.get(axis);
+        }
     }
     fn energy(&self) -> i32 {
         self.pos.sum() * self.vel.sum()
@@ -66,44 +90,494 @@ fn compare(a: i32, b: i32) -> i32 {
     }
 }
 
+// How the moons change each other's velocities.
+trait Law {
+    // Velocity change of a moon at `a` pulled by one at `b`.
+    fn pull(&self, a: &Vec3, b: &Vec3, dims: usize) -> Vec3;
+    // Velocity change of a moon moving at `vel`, on top of the pulls.
+    fn drag(&self, _vel: &Vec3, _dims: usize) -> Vec3 {
+        Vec3::new(0, 0, 0)
+    }
+    // Whether the pull along an axis only depends on positions along that
+    // axis, so that every axis can be simulated on its own.
+    fn separable(&self) -> bool {
+        true
+    }
+}
+
+// The puzzle's gravity: one unit towards the other moon along every axis.
+struct Gravity;
+
+impl Law for Gravity {
+    fn pull(&self, a: &Vec3, b: &Vec3, dims: usize) -> Vec3 {
+        let mut result = Vec3::new(0, 0, 0);
+        for axis in 0..dims {
+            *result.get_mut(axis) = compare(a.get(axis), b.get(axis));
+        }
+        result
+    }
+}
+
+// A spring pulling `strength` units per unit of distance, but never more than
+// `cap` units along an axis.
+struct Capped {
+    strength: i32,
+    cap: i32,
+}
+
+impl Law for Capped {
+    fn pull(&self, a: &Vec3, b: &Vec3, dims: usize) -> Vec3 {
+        let mut result = Vec3::new(0, 0, 0);
+        for axis in 0..dims {
+            let pull = self.strength * (b.get(axis) - a.get(axis));
+            *result.get_mut(axis) = pull.max(-self.cap).min(self.cap);
+        }
+        result
+    }
+}
+
+// Fractional bits of the fixed point numbers used by `InverseSquare`.
+const FIXED: i128 = 1 << 16;
+
+// Newton's gravity, `strength / r^2` towards the other moon. It is worked out
+// in fixed point and rounded to whole units so states stay comparable.
+struct InverseSquare {
+    strength: i32,
+}
+
+impl Law for InverseSquare {
+    fn pull(&self, a: &Vec3, b: &Vec3, dims: usize) -> Vec3 {
+        let mut result = Vec3::new(0, 0, 0);
+        let d = (0..dims)
+            .map(|axis| (b.get(axis) - a.get(axis)) as i128)
+            .collect::<Vec<_>>();
+        let r2 = d.iter().map(|d| d * d).sum::<i128>();
+        if r2 == 0 {
+            return result;
+        }
+        // strength * d / r^3, with r itself in fixed point
+        let r = isqrt(r2 * FIXED * FIXED);
+        for (axis, d) in d.iter().enumerate() {
+            let pull = div_round(self.strength as i128 * d * FIXED, r2 * r);
+            *result.get_mut(axis) = pull as i32;
+        }
+        result
+    }
+    fn separable(&self) -> bool {
+        false
+    }
+}
+
+fn isqrt(n: i128) -> i128 {
+    let mut x = (n as f64).sqrt() as i128;
+    while x * x > n {
+        x -= 1;
+    }
+    while (x + 1) * (x + 1) <= n {
+        x += 1;
+    }
+    x
+}
+
+// `n / d` rounded to the nearest integer, for positive `d`.
+fn div_round(n: i128, d: i128) -> i128 {
+    (n + n.signum() * d / 2) / d
+}
+
+// Friction taking up to `friction` units off the speed along every axis,
+// on top of another law.
+struct Drag {
+    friction: i32,
+    law: Rc<dyn Law>,
+}
+
+impl Law for Drag {
+    fn pull(&self, a: &Vec3, b: &Vec3, dims: usize) -> Vec3 {
+        self.law.pull(a, b, dims)
+    }
+    fn drag(&self, vel: &Vec3, dims: usize) -> Vec3 {
+        let mut result = Vec3::new(0, 0, 0);
+        for axis in 0..dims {
+            let v = vel.get(axis);
+            *result.get_mut(axis) = -v.signum() * v.abs().min(self.friction);
+        }
+        result
+    }
+    fn separable(&self) -> bool {
+        self.law.separable()
+    }
+}
+
+// Laws are written `gravity`, `capped:<strength>:<cap>`,
+// `inverse-square:<strength>` or `drag:<friction>:<law>`.
+fn read_law(spec: &str) -> Result<Rc<dyn Law>, String> {
+    let number = |n: Option<&str>| -> Result<i32, String> {
+        let n = n.ok_or(format!("missing parameter in {}", spec))?;
+        n.parse::<i32>().map_err(|e| format!("{}: {}", n, e))
+    };
+    let mut parts = spec.splitn(3, ':');
+    match parts.next() {
+        Some("gravity") => Ok(Rc::new(Gravity)),
+        Some("capped") => Ok(Rc::new(Capped {
+            strength: number(parts.next())?,
+            cap: number(parts.next())?,
+        })),
+        Some("inverse-square") => Ok(Rc::new(InverseSquare {
+            strength: number(parts.next())?,
+        })),
+        Some("drag") => {
+            let friction = number(parts.next())?;
+            let law = read_law(parts.next().unwrap_or("gravity"))?;
+            Ok(Rc::new(Drag { friction, law }))
+        }
+        _ => Err(format!("unknown law {}", spec)),
+    }
+}
+
+// Missing coordinates, as in `<x=1>` or `<x=1, y=2>`, are zero.
 fn read_moon(input: &str) -> Moon {
-    let moon_regex = Regex::new(r"<x=(-?\d+), y=(-?\d+), z=(-?\d+)>").unwrap();
+    let moon_regex = Regex::new(r"<x=(-?\d+)(?:, y=(-?\d+))?(?:, z=(-?\d+))?>").unwrap();
     let caps = moon_regex.captures(input).unwrap();
-    Moon::new(Vec3::new(caps[1].parse().unwrap()
-                        , caps[2].parse().unwrap()
-                        , caps[3].parse().unwrap())
-              , Vec3::new(0, 0, 0))
+    let coordinate = |i: usize| caps.get(i).map_or(0, |c| c.as_str().parse().unwrap());
+    Moon::new(
+        Vec3::new(coordinate(1), coordinate(2), coordinate(3)),
+        Vec3::new(0, 0, 0),
+    )
+}
+
+// Moons pulling on each other along their first `dims` axes.
+#[derive(Clone)]
+struct System {
+    moons: Vec<Moon>,
+    dims: usize,
+    time: u64,
+    law: Rc<dyn Law>,
 }
 
-fn step_velocities(pos: &[i32]) -> Vec<i32> {
-    let mut result = vec![0; pos.len()];
-    for (i1, p1) in pos.iter().enumerate() {
-        for (i2, p2) in pos.iter().enumerate().skip(i1+1) {
-            result[i1] += compare(*p1, *p2);
-            result[i2] += compare(*p2, *p1);
+impl System {
+    fn new(moons: Vec<Moon>, dims: usize, law: Rc<dyn Law>) -> Self {
+        assert!(
+            (1..=3).contains(&dims),
+            "moons move in 1 to 3 dimensions, not {}",
+            dims
+        );
+        Self {
+            moons,
+            dims,
+            time: 0,
+            law,
         }
     }
-    result
+    fn step(&mut self) {
+        for i in 0..self.moons.len() {
+            for j in (i + 1)..self.moons.len() {
+                let m1 = self.moons[i];
+                let m2 = self.moons[j];
+                self.moons[i].update_velocity(&m2, self.law.as_ref(), self.dims);
+                self.moons[j].update_velocity(&m1, self.law.as_ref(), self.dims);
+            }
+        }
+        for cur in self.moons.iter_mut() {
+            cur.slow_down(self.law.as_ref(), self.dims);
+            cur.walk(self.dims);
+        }
+        self.time += 1;
+    }
+    fn run(&mut self, steps: u64) {
+        for _ in 0..steps {
+            self.step();
+        }
+    }
+    // This state followed by every later one.
+    fn states(&self) -> impl Iterator<Item = System> {
+        std::iter::successors(Some(self.clone()), |s| {
+            let mut next = s.clone();
+            next.step();
+            Some(next)
+        })
+    }
+    fn energy(&self) -> i32 {
+        self.moons.iter().fold(0, |acc, m| acc + m.energy())
+    }
+    // The system seen along a single axis, which moves on its own as long as
+    // the law is separable.
+    fn project(&self, axis: usize) -> System {
+        let moons = self
+            .moons
+            .iter()
+            .map(|m| {
+                Moon::new(
+                    Vec3::new(m.pos.get(axis), 0, 0),
+                    Vec3::new(m.vel.get(axis), 0, 0),
+                )
+            })
+            .collect();
+        System {
+            moons,
+            dims: 1,
+            time: self.time,
+            law: self.law.clone(),
+        }
+    }
+    // Whether some moon got so far or so fast that it is not coming back.
+    fn escaped(&self) -> bool {
+        self.moons.iter().any(|m| {
+            (0..self.dims)
+                .any(|axis| m.pos.get(axis).abs() > ESCAPE || m.vel.get(axis).abs() > ESCAPE)
+        })
+    }
+    fn next(&self) -> System {
+        let mut next = self.clone();
+        next.step();
+        next
+    }
+}
+
+// The states of a system eventually go round a loop: from step `start` on, they
+// repeat every `length` steps.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct Cycle {
+    start: u64,
+    length: u64,
+}
+
+impl Cycle {
+    // Independent parts of a system are all on their loops from the latest
+    // start on, and from there line up again every lcm of the lengths.
+    fn combine(self, other: Cycle) -> Cycle {
+        Cycle {
+            start: self.start.max(other.start),
+            length: self.length.lcm(&other.length),
+        }
+    }
+    // Steps until the system is in a state it has already been in.
+    fn first_repeat(&self) -> u64 {
+        self.start + self.length
+    }
 }
 
-fn repeat_axis(mut pos: Vec<i32>) -> u64 {
-    let mut velocities = vec![0i32; pos.len()];
-    let end_velocities = velocities.clone();
-    let mut steps = 0;
-    loop {
-        let velocity_changes = step_velocities(&pos);
-        for (v, c) in velocities.iter_mut().zip(velocity_changes) {
-            *v += c;
+// Coordinates beyond this are taken as a moon flying off.
+const ESCAPE: i32 = 1 << 20;
+
+// Steps after which we give up looking for a cycle.
+const CYCLE_LIMIT: u64 = 100_000_000;
+
+// Brent's cycle detection over the states following `system`.
+fn brent(system: &System) -> Option<Cycle> {
+    let mut power = 1;
+    let mut length = 1;
+    let mut tortoise = system.clone();
+    let mut hare = system.next();
+    while tortoise.moons != hare.moons {
+        if hare.escaped() || hare.time > CYCLE_LIMIT {
+            return None;
         }
-        for (p, v) in pos.iter_mut().zip(velocities.iter()) {
-            *p += v;
+        if power == length {
+            tortoise = hare.clone();
+            power *= 2;
+            length = 0;
         }
-        steps += 1;
-        if velocities == end_velocities {
-            break;
+        hare.step();
+        length += 1;
+    }
+    let mut tortoise = system.clone();
+    let mut hare = system.clone();
+    hare.run(length);
+    let mut start = 0;
+    while tortoise.moons != hare.moons {
+        tortoise.step();
+        hare.step();
+        start += 1;
+    }
+    Some(Cycle { start, length })
+}
+
+fn cycle(system: &System) -> Option<Cycle> {
+    if !system.law.separable() {
+        return brent(system);
+    }
+    (0..system.dims)
+        .map(|axis| brent(&system.project(axis)))
+        .try_fold(
+            Cycle {
+                start: 0,
+                length: 1,
+            },
+            |acc, cycle| Some(acc.combine(cycle?)),
+        )
+}
+
+// Simulates the system to check that it does loop as `cycle` says, and does
+// not start looping any earlier.
+fn validate(system: &System, cycle: Cycle) -> bool {
+    let mut at = system.clone();
+    at.run(cycle.start);
+    let mut after = at.clone();
+    after.run(cycle.length);
+    if at.moons != after.moons {
+        return false;
+    }
+    if cycle.start == 0 {
+        return true;
+    }
+    let mut before = system.clone();
+    before.run(cycle.start - 1);
+    let mut previous = before.clone();
+    previous.run(cycle.length);
+    previous.moons != before.moons
+}
+
+// Beyond this many steps validating the whole system takes too long, and only
+// the cycle of each axis is checked.
+const VALIDATE_LIMIT: u64 = 10_000_000;
+
+// One line per moon per step, with the position and velocity on every axis.
+fn to_csv(history: &[System]) -> String {
+    let dims = history.first().map_or(3, |s| s.dims);
+    let names = ["x", "y", "z"];
+    let mut result = String::from("step,moon");
+    for name in names.iter().take(dims) {
+        result.push_str(&format!(",{}", name));
+    }
+    for name in names.iter().take(dims) {
+        result.push_str(&format!(",v{}", name));
+    }
+    result.push_str(",energy\n");
+    for system in history {
+        for (i, moon) in system.moons.iter().enumerate() {
+            result.push_str(&format!("{},{}", system.time, i));
+            for axis in 0..dims {
+                result.push_str(&format!(",{}", moon.pos.get(axis)));
+            }
+            for axis in 0..dims {
+                result.push_str(&format!(",{}", moon.vel.get(axis)));
+            }
+            result.push_str(&format!(",{}\n", moon.energy()));
         }
     }
-    steps * 2
+    result
+}
+
+// Size of a projection in the SVG, and the space around it.
+const PANEL: i64 = 240;
+const MARGIN: i64 = 30;
+const COLORS: [&str; 6] = [
+    "#e6194b", "#3cb44b", "#4363d8", "#f58231", "#911eb4", "#42d4f4",
+];
+
+// Trajectories of the moons projected on every pair of axes, one row per run
+// so different laws can be told apart side by side. A single axis is plotted
+// against time.
+fn to_svg(runs: &[(String, Vec<System>)]) -> String {
+    let dims = runs[0].1[0].dims;
+    let names = ["x", "y", "z", "t"];
+    // axis 3 is time
+    let planes = match dims {
+        1 => vec![(3, 0)],
+        2 => vec![(0, 1)],
+        _ => vec![(0, 1), (0, 2), (1, 2)],
+    };
+    let value = |s: &System, m: &Moon, axis: usize| {
+        if axis == 3 {
+            s.time as i64
+        } else {
+            m.pos.get(axis) as i64
+        }
+    };
+    let cell = PANEL + 2 * MARGIN;
+    let mut result = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n",
+        cell * planes.len() as i64,
+        cell * runs.len() as i64
+    );
+    for (column, (h, v)) in planes.iter().enumerate() {
+        // every run of a projection shares its scale
+        let points = runs
+            .iter()
+            .flat_map(|(_, history)| {
+                history.iter().flat_map(move |s| {
+                    s.moons
+                        .iter()
+                        .map(move |m| (value(s, m, *h), value(s, m, *v)))
+                })
+            })
+            .collect::<Vec<_>>();
+        let (min_h, max_h) = (
+            points.iter().map(|p| p.0).min().unwrap(),
+            points.iter().map(|p| p.0).max().unwrap(),
+        );
+        let (min_v, max_v) = (
+            points.iter().map(|p| p.1).min().unwrap(),
+            points.iter().map(|p| p.1).max().unwrap(),
+        );
+        let scale = |n: i64, min: i64, max: i64| n.saturating_sub(min) * PANEL / (max - min).max(1);
+        for (row, (label, history)) in runs.iter().enumerate() {
+            let (left, top) = (column as i64 * cell + MARGIN, row as i64 * cell + MARGIN);
+            result.push_str(&format!("  <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"#999\"/>\n",
+                                     left, top, PANEL, PANEL));
+            result.push_str(&format!(
+                "  <text x=\"{}\" y=\"{}\" font-size=\"12\">{} ({}, {})</text>\n",
+                left,
+                top - 8,
+                label,
+                names[*h],
+                names[*v]
+            ));
+            for i in 0..history[0].moons.len() {
+                let path = history
+                    .iter()
+                    .map(|s| {
+                        let m = &s.moons[i];
+                        format!(
+                            "{},{}",
+                            left + scale(value(s, m, *h), min_h, max_h),
+                            top + PANEL - scale(value(s, m, *v), min_v, max_v)
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                result.push_str(&format!(
+                    "  <polyline points=\"{}\" fill=\"none\" stroke=\"{}\"/>\n",
+                    path.join(" "),
+                    COLORS[i % COLORS.len()]
+                ));
+            }
+        }
+    }
+    result.push_str("</svg>\n");
+    result
+}
+
+// Total energy after `steps` steps.
+fn first(system: &System, steps: u64) -> i32 {
+    let mut system = system.clone();
+    system.run(steps);
+    system.energy()
+}
+
+// Steps until the system is back to a state it has been in, if it ever is.
+fn second(system: &System) -> Option<u64> {
+    let cycle = cycle(system)?;
+    let checked = if cycle.first_repeat() <= VALIDATE_LIMIT || !system.law.separable() {
+        validate(system, cycle)
+    } else {
+        (0..system.dims).all(|axis| {
+            let axis = system.project(axis);
+            matches!(brent(&axis), Some(cycle) if validate(&axis, cycle))
+        })
+    };
+    assert!(
+        checked,
+        "simulation disagrees with the cycle found: {:?}",
+        cycle
+    );
+    Some(cycle.first_repeat())
+}
+
+// Value of a `--name value` option given anywhere on the command line.
+fn option(name: &str) -> Option<String> {
+    let args = env::args().collect::<Vec<_>>();
+    let i = args.iter().position(|a| a == name)?;
+    args.get(i + 1).cloned()
 }
 
 fn main() {
@@ -111,30 +585,133 @@ fn main() {
     let reader = BufReader::new(f);
     let mut moons = Vec::new();
     for line in reader.lines() {
-        moons.push(read_moon(&line.unwrap()));
-    }
-
-    // part 1
-    // let n = env::args().nth(2).unwrap_or(10.to_string()).parse::<i32>().unwrap();
-    // for _ in 0..n {
-        // for i in 0..moons.len() {
-        //     for j in (i+1)..moons.len() {
-        //         let m1 = moons[i];
-        //         let m2 = moons[j];
-        //         moons[i].update_velocity(&m2);
-        //         moons[j].update_velocity(&m1);
-        //     }
-        // }
-        // for cur in moons.iter_mut() {
-        //     cur.walk();
-        // }
-    // }
-    // let energy = moons.iter().fold(0, |acc, m| acc + m.energy());
-    // println!("after {} steps, energy = {}", n, energy);
-
-    // part 2
-    let res = 1.lcm(&repeat_axis(moons.iter().map(|m| m.pos.x).collect()))
-        .lcm(&repeat_axis(moons.iter().map(|m| m.pos.y).collect()))
-        .lcm(&repeat_axis(moons.iter().map(|m| m.pos.z).collect()));
-    println!("{}", res);
+        let line = line.unwrap();
+        if !line.trim().is_empty() {
+            moons.push(read_moon(&line));
+        }
+    }
+    let steps = env::args()
+        .nth(2)
+        .unwrap_or(1000.to_string())
+        .parse::<u64>()
+        .unwrap();
+    let dims = env::args()
+        .nth(3)
+        .unwrap_or(3.to_string())
+        .parse::<usize>()
+        .unwrap();
+    // several laws can be given, separated by commas, to compare them
+    let specs = option("--law").unwrap_or("gravity".to_string());
+    // histories are only kept when something is going to be exported
+    let export = option("--csv").is_some() || option("--svg").is_some();
+    let mut runs = Vec::new();
+    for spec in specs.split(',') {
+        let law = match read_law(spec) {
+            Ok(law) => law,
+            Err(error) => {
+                println!("{}", error);
+                return;
+            }
+        };
+        let system = System::new(moons.clone(), dims, law);
+        println!(
+            "{}: after {} steps, energy = {}",
+            spec,
+            steps,
+            first(&system, steps)
+        );
+        match second(&system) {
+            Some(period) => println!("{}: repeats after {} steps", spec, period),
+            None => println!("{}: does not repeat", spec),
+        }
+        if export {
+            runs.push((
+                spec.to_string(),
+                system.states().take(steps as usize + 1).collect::<Vec<_>>(),
+            ));
+        }
+    }
+    // the CSV export holds the history under the first law
+    if let Some(name) = option("--csv") {
+        fs::write(name, to_csv(&runs[0].1)).unwrap();
+    }
+    if let Some(name) = option("--svg") {
+        fs::write(name, to_svg(&runs)).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_moons(input: &str) -> Vec<Moon> {
+        input.lines().map(read_moon).collect()
+    }
+
+    // The sample systems from the puzzle, with the energy after some steps and
+    // how long they take to repeat.
+    const SAMPLES: [(&str, u64, i32, u64); 2] = [
+        (
+            "<x=-1, y=0, z=2>
+<x=2, y=-10, z=-7>
+<x=4, y=-8, z=8>
+<x=3, y=5, z=-1>",
+            10,
+            179,
+            2772,
+        ),
+        (
+            "<x=-8, y=-10, z=0>
+<x=5, y=5, z=10>
+<x=2, y=-7, z=3>
+<x=9, y=-8, z=-3>",
+            100,
+            1940,
+            4_686_774_924,
+        ),
+    ];
+
+    // a spring capped at one unit is the puzzle's gravity again
+    const GRAVITIES: [&str; 2] = ["gravity", "capped:1:1"];
+
+    #[test]
+    fn energy() {
+        for spec in GRAVITIES.iter() {
+            for (input, steps, energy, _) in SAMPLES.iter() {
+                let system = System::new(read_moons(input), 3, read_law(spec).unwrap());
+                assert_eq!(first(&system, *steps), *energy, "under {}", spec);
+            }
+        }
+    }
+
+    #[test]
+    fn period() {
+        for spec in GRAVITIES.iter() {
+            for (input, _, _, period) in SAMPLES.iter() {
+                let system = System::new(read_moons(input), 3, read_law(spec).unwrap());
+                assert_eq!(second(&system), Some(*period), "under {}", spec);
+            }
+        }
+    }
+
+    #[test]
+    fn state_after_ten_steps() {
+        let system = System::new(read_moons(SAMPLES[0].0), 3, Rc::new(Gravity));
+        let after = system.states().nth(10).unwrap();
+        let expected = Moon::new(Vec3::new(2, 1, -3), Vec3::new(-3, -2, 1));
+        assert_eq!(after.moons[0], expected);
+    }
+
+    // friction loses energy, so these only loop after settling down
+    #[test]
+    fn cycles_under_drag() {
+        let expected = [("drag:1:gravity", 11, 12), ("drag:2:capped:1:3", 15, 4)];
+        for (spec, start, length) in expected.iter() {
+            let system = System::new(read_moons(SAMPLES[0].0), 3, read_law(spec).unwrap());
+            let cycle = cycle(&system).unwrap();
+            let found = (cycle.start, cycle.length);
+            assert_eq!(found, (*start, *length), "under {}", spec);
+            assert!(validate(&system, cycle), "under {}", spec);
+        }
+    }
 }
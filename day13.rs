@@ -22,6 +22,10 @@ impl Tile {
             _ => panic!("unknown tile id: {}", id),
         }
     }
+
+    fn id(self) -> i128 {
+        self as i128
+    }
 }
 
 struct Arcade {
@@ -83,14 +87,151 @@ fn parse(input: &str) -> Vec<i128> {
     result
 }
 
-fn second() {
-    let input = fs::read_to_string(env::args().nth(1).unwrap()).unwrap();
-    let mut memory = parse(&input);
+// The game keeps its screen as a row-major grid of tile ids, followed by a
+// table of the same size holding the points of every cell. Breaking the block
+// at linear position `cell` awards `table[(multiplier * cell + offset) % size]`.
+struct ScoreTable {
+    width: usize,
+    height: usize,
+    grid: usize,
+    table: usize,
+    multiplier: i128,
+    offset: i128,
+    column_major: bool,
+}
+
+impl ScoreTable {
+    fn locate(memory: &[i128], tiles: &HashMap<(i128, i128), Tile>) -> Option<Self> {
+        let width = tiles.keys().map(|k| k.0).max()? as usize + 1;
+        let height = tiles.keys().map(|k| k.1).max()? as usize + 1;
+        let size = width * height;
+        let mut screen = vec![Tile::Empty.id(); size];
+        for (k, v) in tiles.iter() {
+            screen[k.1 as usize * width + k.0 as usize] = v.id();
+        }
+        let grid = memory.windows(size).position(|w| w == &screen[..])?;
+        let table = grid + size;
+        if memory.len() < table + size {
+            return None;
+        }
+        // look for the hashing arithmetic: `cell = a * dim + b` where dim is
+        // one of the screen dimensions, then `multiplier * cell + offset`
+        let code = disassemble(&memory[..grid]);
+        for (i, (op, params)) in code.iter().enumerate() {
+            let dim = match (op, immediate(params)) {
+                (2, Some(d)) if d == width as i128 || d == height as i128 => d,
+                _ => continue,
+            };
+            let rest = &code[i + 1..code.len().min(i + 6)];
+            let mul = rest
+                .iter()
+                .position(|(op, params)| *op == 2 && immediate(params).is_some());
+            if let Some(j) = mul {
+                if let Some((1, params)) = rest.get(j + 1) {
+                    if let Some(offset) = immediate(params) {
+                        return Some(ScoreTable {
+                            width,
+                            height,
+                            grid,
+                            table,
+                            multiplier: immediate(&rest[j].1).unwrap(),
+                            offset,
+                            column_major: dim == height as i128 && width != height,
+                        });
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn final_score(&self, memory: &[i128]) -> i128 {
+        let size = self.width * self.height;
+        let mut score = 0;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if memory[self.grid + y * self.width + x] != Tile::Block.id() {
+                    continue;
+                }
+                let cell = if self.column_major {
+                    x * self.height + y
+                } else {
+                    y * self.width + x
+                } as i128;
+                let idx = (self.multiplier * cell + self.offset).rem_euclid(size as i128);
+                score += memory[self.table + idx as usize];
+            }
+        }
+        score
+    }
+}
+
+fn disassemble(memory: &[i128]) -> Vec<(i128, Vec<(ParamMode, i128)>)> {
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < memory.len() {
+        let (a, b, c, op) = match memory[i] {
+            n if n > 0 && n / 100 % 10 < 3 && n / 1000 % 10 < 3 && n / 10000 < 3 => decode(n),
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        let len = match op {
+            1 | 2 | 7 | 8 => 3,
+            3 | 4 | 9 => 1,
+            5 | 6 => 2,
+            _ => 0,
+        };
+        let params = vec![c, b, a]
+            .into_iter()
+            .take(len)
+            .enumerate()
+            .filter_map(|(k, m)| memory.get(i + k + 1).map(|v| (m, *v)))
+            .collect();
+        result.push((op, params));
+        i += len + 1;
+    }
+    result
+}
+
+fn immediate(params: &[(ParamMode, i128)]) -> Option<i128> {
+    params.iter().find_map(|(m, v)| match m {
+        ParamMode::Immediate => Some(*v),
+        _ => None,
+    })
+}
+
+fn play(mut memory: Vec<i128>) -> i128 {
     memory[0] = 2;
     let arcade = Arcade::new();
     let mut intcode = IntCode::new(memory, arcade);
     intcode.run();
-    println!("{}", intcode.bus.score);
+    intcode.bus.score
+}
+
+fn analyze() {
+    let input = fs::read_to_string(env::args().nth(1).unwrap()).unwrap();
+    let memory = parse(&input);
+    let mut intcode = IntCode::new(memory.clone(), Arcade::new());
+    intcode.run();
+    match ScoreTable::locate(&memory, &intcode.bus.tiles) {
+        Some(table) => {
+            let score = table.final_score(&memory);
+            let played = play(memory);
+            if score == played {
+                println!("{}", score);
+            } else {
+                println!("{} (simulation says {})", score, played);
+            }
+        }
+        None => println!("score table not found"),
+    }
+}
+
+fn second() {
+    let input = fs::read_to_string(env::args().nth(1).unwrap()).unwrap();
+    println!("{}", play(parse(&input)));
 }
 
 fn first() {
@@ -111,8 +252,13 @@ fn first() {
 }
 
 fn main() {
-    first();
-    second();
+    match env::args().nth(2).as_deref() {
+        Some("analyze") => analyze(),
+        _ => {
+            first();
+            second();
+        }
+    }
 }
 
 enum ParamMode {
@@ -1,7 +1,15 @@
-use std::collections::{HashMap, HashSet};
+#[cfg(feature = "image")]
+use image::codecs::gif::{GifEncoder, Repeat};
+#[cfg(feature = "json")]
+use serde_json::{json, Value};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
 use std::env;
 use std::fmt;
 use std::fs;
+use std::hash::Hash;
+use std::thread;
+use std::time::Duration;
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 enum Tile {
@@ -19,6 +27,30 @@ impl Tile {
             _ => panic!("Invalid tile type: {}", v),
         }
     }
+    fn glyph(&self) -> char {
+        match *self {
+            Tile::Wall => 'X',
+            Tile::Empty => 'o',
+            Tile::Found => '@',
+        }
+    }
+    fn from_glyph(c: char) -> Option<Tile> {
+        match c {
+            'X' => Some(Tile::Wall),
+            'o' | 'D' => Some(Tile::Empty),
+            '@' => Some(Tile::Found),
+            _ => None,
+        }
+    }
+    #[cfg(feature = "json")]
+    fn from_name(name: &str) -> Tile {
+        match name {
+            "Wall" => Tile::Wall,
+            "Empty" => Tile::Empty,
+            "Found" => Tile::Found,
+            _ => panic!("Invalid tile name: {}", name),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -29,14 +61,20 @@ enum Direction {
     East,
 }
 
+const DIRECTIONS: [Direction; 4] = [
+    Direction::North,
+    Direction::South,
+    Direction::West,
+    Direction::East,
+];
+
 impl Direction {
-    fn from_step(step: (i128, i128)) -> Direction {
-        match step {
-            (0, 1) => Direction::North,
-            (0, -1) => Direction::South,
-            (-1, 0) => Direction::West,
-            (1, 0) => Direction::East,
-            _ => panic!("Invalid step: {:?}", step),
+    fn opposite(&self) -> Direction {
+        match *self {
+            Direction::North => Direction::South,
+            Direction::South => Direction::North,
+            Direction::West => Direction::East,
+            Direction::East => Direction::West,
         }
     }
     fn to_step(&self) -> (i128, i128) {
@@ -71,12 +109,7 @@ impl Coordinate {
         Coordinate::new(self.x + step.0, self.y + step.1)
     }
     fn neighbors(&self) -> Vec<Self> {
-        vec![
-            self.step(Direction::North.to_step()),
-            self.step(Direction::South.to_step()),
-            self.step(Direction::West.to_step()),
-            self.step(Direction::East.to_step()),
-        ]
+        DIRECTIONS.iter().map(|d| self.step(d.to_step())).collect()
     }
 }
 
@@ -86,287 +119,561 @@ impl fmt::Display for Coordinate {
     }
 }
 
-fn get_step(current: &Coordinate, next: &Coordinate) -> (i128, i128) {
-    let result = (next.x - current.x, next.y - current.y);
-    match result {
-        (0, 1) | (0, -1) | (1, 0) | (-1, 0) => result,
-        _ => panic!("unknown step {:?}", result),
-    }
+type Cost = u64;
+
+// Anything that moves the droid one step and reports what it ran into.
+trait Area {
+    fn step(&mut self, direction: Direction) -> Tile;
 }
 
-struct Droid2 {
-    current: Coordinate,
-    next: Option<Coordinate>,
-    direction: Direction,
-    map: HashMap<Coordinate, (Tile, Cost)>,
-    oxygen_system: Option<Coordinate>,
+struct Remote {
+    command: i128,
+    status: Option<Tile>,
 }
 
-impl Droid2 {
+impl Remote {
     fn new() -> Self {
-        let current = Coordinate::new(0, 0);
-        let next = Coordinate::new(0, 1);
-        let mut map = HashMap::new();
-        map.insert(current, (Tile::Empty, 0));
-
-        Droid2 {
-            current: current,
-            next: Some(next),
-            direction: Direction::from_step(get_step(&current, &next)),
-            map: map,
-            oxygen_system: None,
+        Remote {
+            command: 0,
+            status: None,
         }
     }
-    fn get_cost(&self, c: &Coordinate) -> Option<u64> {
-        let e = self.map.get(c)?;
-        Some(e.1)
+}
+
+impl Bus for Remote {
+    fn input(&self) -> i128 {
+        self.command
     }
-    fn get_tile(&self, c: &Coordinate) -> Option<Tile> {
-        let e = self.map.get(c)?;
-        Some(e.0)
+    fn output(&mut self, v: i128) {
+        self.status = Some(Tile::parse(v));
     }
-    fn get_next(&mut self) -> Option<Coordinate> {
-        let c = self
-            .current
-            .neighbors()
-            .into_iter()
-            .filter(|c| !self.map.contains_key(c))
-            .next();
-        if let Some(_) = c {
-            return c;
-        }
-        let result = self
-            .current
-            .neighbors()
-            .into_iter()
-            .filter(|c| self.map.get(c).unwrap().0 != Tile::Wall)
-            .min_by(|x, y| self.get_cost(&x).unwrap().cmp(&self.get_cost(&y).unwrap()));
+}
 
-        if let Some(r) = result {
-            if self.get_cost(&r).unwrap() == 0 {
-                return None;
-            }
-            for n in self.current.neighbors().iter() {
-                if *n != r {
-                    let v = self.map.get_mut(n).unwrap();
-                    if (*v).0 != Tile::Wall {
-                        (*v).1 = 1_000_000_000;
-                    }
-                }
-            }
-        }
-        result
-    }
-    fn get_time(&self) -> Option<u64> {
-        let os = self.oxygen_system?;
-        let mut result = 0;
-        let mut visited: HashSet<Coordinate> = HashSet::new();
-        let mut frontier: Vec<Coordinate> = os
-            .neighbors()
-            .into_iter()
-            .filter(|i| self.get_tile(i) == Some(Tile::Empty))
-            .collect();
-        visited.insert(os);
-
-        while !frontier.is_empty() {
-            let mut new_frontier = Vec::new();
-            for c in frontier.into_iter() {
-                visited.insert(c);
-                let mut neighbors = c
-                    .neighbors()
-                    .into_iter()
-                    .filter(|i| self.get_tile(i) == Some(Tile::Empty) && !visited.contains(i))
-                    .collect();
-                new_frontier.append(&mut neighbors);
+impl Area for IntCode<Remote> {
+    fn step(&mut self, direction: Direction) -> Tile {
+        self.bus.command = direction.to_command();
+        self.bus.status = None;
+        while self.bus.status.is_none() {
+            if self.execute() == 99 {
+                panic!("droid halted while moving {:?}", direction);
             }
-            frontier = new_frontier;
-            result += 1;
         }
-        Some(result)
+        self.bus.status.unwrap()
     }
 }
 
-impl Bus for Droid2 {
-    fn input(&self) -> i128 {
-        if let None = self.next {
-            return 99;
-        }
-        self.direction.to_command()
-    }
-    fn output(&mut self, v: i128) {
-        let tile = Tile::parse(v);
-        let new_coord = self.current.step(self.direction.to_step());
-        let mut cost = self.map.get(&self.current).unwrap().1 + 1;
-        match tile {
-            Tile::Found => {
-                self.oxygen_system = Some(new_coord);
-
-                self.current = new_coord;
-                self.next = self.get_next();
-                if let Some(n) = self.next {
-                    self.direction = Direction::from_step(get_step(&self.current, &n));
+// Depth-first walk over the whole area, backtracking along the way we came
+// once every neighbor of the current cell has been probed.
+fn explore<A: Area>(area: &mut A) -> HashMap<Coordinate, (Tile, Cost)> {
+    let origin = Coordinate::new(0, 0);
+    let mut tiles = HashMap::new();
+    tiles.insert(origin, Tile::Empty);
+    let mut current = origin;
+    let mut path: Vec<Direction> = Vec::new();
+    loop {
+        let next = DIRECTIONS
+            .iter()
+            .find(|d| !tiles.contains_key(&current.step(d.to_step())));
+        match next {
+            Some(d) => {
+                let c = current.step(d.to_step());
+                let tile = area.step(*d);
+                tiles.insert(c, tile);
+                if tile != Tile::Wall {
+                    current = c;
+                    path.push(*d);
                 }
             }
-            Tile::Empty => {
-                self.current = new_coord;
-                self.next = self.get_next();
-                if let Some(n) = self.next {
-                    self.direction = Direction::from_step(get_step(&self.current, &n));
+            None => match path.pop() {
+                Some(d) => {
+                    let back = d.opposite();
+                    area.step(back);
+                    current = current.step(back.to_step());
                 }
+                None => break,
+            },
+        }
+    }
+    with_costs(&tiles, origin)
+}
+
+// Attaches to every cell the number of moves needed to reach it from `origin`;
+// a wall costs one more than the cheapest cell it was probed from.
+fn with_costs(
+    tiles: &HashMap<Coordinate, Tile>,
+    origin: Coordinate,
+) -> HashMap<Coordinate, (Tile, Cost)> {
+    let mut map = HashMap::new();
+    map.insert(origin, (tiles[&origin], 0));
+    let mut queue = VecDeque::new();
+    queue.push_back(origin);
+    while let Some(c) = queue.pop_front() {
+        let cost = map[&c].1 + 1;
+        for n in c.neighbors() {
+            if map.contains_key(&n) {
+                continue;
             }
-            Tile::Wall => {
-                self.next = self.get_next();
-                if let Some(n) = self.next {
-                    self.direction = Direction::from_step(get_step(&self.current, &n));
+            if let Some(tile) = tiles.get(&n) {
+                map.insert(n, (*tile, cost));
+                if *tile != Tile::Wall {
+                    queue.push_back(n);
                 }
-                cost = 1_000_000_000;
             }
         }
-        if !self.map.contains_key(&new_coord) {
-            self.map.insert(new_coord, (tile, cost));
-        }
     }
+    map
 }
 
-type Cost = u64;
+fn find(map: &HashMap<Coordinate, (Tile, Cost)>, tile: Tile) -> Option<Coordinate> {
+    map.iter()
+        .find_map(|(k, v)| if v.0 == tile { Some(*k) } else { None })
+}
 
-struct Droid1 {
-    current: Coordinate,
-    next: Coordinate,
-    direction: Direction,
-    map: HashMap<Coordinate, (Tile, Cost)>,
-    oxygen_system: Option<Coordinate>,
+fn is_open(map: &HashMap<Coordinate, (Tile, Cost)>, c: &Coordinate) -> bool {
+    !matches!(map.get(c), Some((Tile::Wall, _)) | None)
 }
 
-impl Droid1 {
-    fn new() -> Self {
-        let current = Coordinate::new(0, 0);
-        let next = Coordinate::new(0, 1);
-        let mut map = HashMap::new();
-        map.insert(current, (Tile::Empty, 0));
-
-        Droid1 {
-            current: current,
-            next: next,
-            direction: Direction::from_step(get_step(&current, &next)),
-            map: map,
-            oxygen_system: None,
+// Breadth-first search from `from`, returning for every reachable open cell
+// the cell it was reached from and its distance.
+fn search(
+    map: &HashMap<Coordinate, (Tile, Cost)>,
+    from: Coordinate,
+) -> HashMap<Coordinate, (Coordinate, Cost)> {
+    let mut visited = HashMap::new();
+    if !is_open(map, &from) {
+        return visited;
+    }
+    visited.insert(from, (from, 0));
+    let mut queue = VecDeque::new();
+    queue.push_back(from);
+    while let Some(c) = queue.pop_front() {
+        let cost = visited[&c].1 + 1;
+        for n in c.neighbors() {
+            if is_open(map, &n) && !visited.contains_key(&n) {
+                visited.insert(n, (c, cost));
+                queue.push_back(n);
+            }
         }
     }
-    fn get_cost(&self, c: &Coordinate) -> Option<u64> {
-        let e = self.map.get(c)?;
-        Some(e.1)
+    visited
+}
+
+fn shortest_path(
+    map: &HashMap<Coordinate, (Tile, Cost)>,
+    from: Coordinate,
+    to: Coordinate,
+) -> Option<Vec<Coordinate>> {
+    let visited = search(map, from);
+    let mut current = to;
+    let mut path = vec![current];
+    while current != from {
+        current = visited.get(&current)?.0;
+        path.push(current);
+    }
+    path.reverse();
+    Some(path)
+}
+
+fn farthest(
+    map: &HashMap<Coordinate, (Tile, Cost)>,
+    from: Coordinate,
+) -> Option<(Coordinate, Cost)> {
+    search(map, from)
+        .into_iter()
+        .map(|(k, v)| (k, v.1))
+        .max_by_key(|v| v.1)
+}
+
+fn fill_time(map: &HashMap<Coordinate, (Tile, Cost)>, from: Coordinate) -> Option<Cost> {
+    oxygen(map, &[(from, 0)], &HashMap::new()).duration()
+}
+
+// When every reached cell filled up, and the order cells filled in, minute by
+// minute.
+struct Flood<C> {
+    times: HashMap<C, Cost>,
+    timeline: Vec<Vec<C>>,
+}
+
+impl<C: Copy + Eq + Hash> Flood<C> {
+    fn duration(&self) -> Option<Cost> {
+        self.times.values().max().copied()
+    }
+    fn last(&self) -> Option<C> {
+        self.timeline
+            .iter()
+            .rev()
+            .find_map(|cells| cells.last().copied())
     }
-    fn get_next(&mut self) -> Coordinate {
-        let c = self
-            .current
-            .neighbors()
-            .into_iter()
-            .filter(|c| !self.map.contains_key(c))
-            .next();
-        if let Some(result) = c {
-            return result;
+}
+
+// Each source starts filling at its own minute and every minute filled cells
+// spread to their neighbors, except doors which stay shut until the minute
+// they open.
+fn flood<C, F>(sources: &[(C, Cost)], doors: &HashMap<C, Cost>, neighbors: F) -> Flood<C>
+where
+    C: Copy + Eq + Hash,
+    F: Fn(&C) -> Vec<C>,
+{
+    let opens = |c: &C| doors.get(c).copied().unwrap_or(0);
+    let mut times = HashMap::new();
+    let mut timeline: Vec<Vec<C>> = Vec::new();
+    let mut cells = Vec::new();
+    let mut heap = BinaryHeap::new();
+    for (c, t) in sources {
+        heap.push(Reverse(((*t).max(opens(c)), cells.len())));
+        cells.push(*c);
+    }
+    while let Some(Reverse((t, i))) = heap.pop() {
+        let c = cells[i];
+        if times.contains_key(&c) {
+            continue;
         }
-        let result = self
-            .current
-            .neighbors()
-            .into_iter()
-            .filter(|c| self.map.get(c).unwrap().0 == Tile::Empty)
-            .min_by(|x, y| self.get_cost(&x).unwrap().cmp(&self.get_cost(&y).unwrap()))
-            .unwrap();
-        for n in self.current.neighbors().iter() {
-            if *n != result {
-                let v = self.map.get_mut(n).unwrap();
-                if (*v).0 == Tile::Empty {
-                    (*v).1 = 1_000_000_000;
-                }
+        times.insert(c, t);
+        if timeline.len() <= t as usize {
+            timeline.resize(t as usize + 1, Vec::new());
+        }
+        timeline[t as usize].push(c);
+        for n in neighbors(&c) {
+            if !times.contains_key(&n) {
+                heap.push(Reverse(((t + 1).max(opens(&n)), cells.len())));
+                cells.push(n);
             }
         }
-        result
     }
+    Flood { times, timeline }
 }
 
-impl Bus for Droid1 {
-    fn input(&self) -> i128 {
-        if let Some(_) = self.oxygen_system {
-            return 99;
+fn oxygen(
+    map: &HashMap<Coordinate, (Tile, Cost)>,
+    sources: &[(Coordinate, Cost)],
+    doors: &HashMap<Coordinate, Cost>,
+) -> Flood<Coordinate> {
+    flood(sources, doors, |c| {
+        c.neighbors()
+            .into_iter()
+            .filter(|n| is_open(map, n) || doors.contains_key(n))
+            .collect()
+    })
+}
+
+// Reads `x,y@minute` as a source and `door:x,y@minute` as a door.
+fn parse_flood(specs: &[String]) -> (Vec<(Coordinate, Cost)>, HashMap<Coordinate, Cost>) {
+    let mut sources = Vec::new();
+    let mut doors = HashMap::new();
+    for spec in specs {
+        let (door, spec) = match spec.strip_prefix("door:") {
+            Some(rest) => (true, rest),
+            None => (false, spec.as_str()),
+        };
+        let mut parts = spec.split(&[',', '@'][..]);
+        let x = parts.next().unwrap().parse::<i128>().unwrap();
+        let y = parts.next().unwrap().parse::<i128>().unwrap();
+        let minute = parts.next().map_or(0, |t| t.parse::<Cost>().unwrap());
+        if door {
+            doors.insert(Coordinate::new(x, y), minute);
+        } else {
+            sources.push((Coordinate::new(x, y), minute));
         }
-        self.direction.to_command()
     }
-    fn output(&mut self, v: i128) {
-        let tile = Tile::parse(v);
-        let new_coord = self.current.step(self.direction.to_step());
-        let mut cost = self.map.get(&self.current).unwrap().1 + 1;
-        match tile {
-            Tile::Found => {
-                self.oxygen_system = Some(new_coord);
-            }
-            Tile::Empty => {
-                self.current = new_coord;
-                self.next = self.get_next();
-                self.direction = Direction::from_step(get_step(&self.current, &self.next));
-            }
-            Tile::Wall => {
-                self.next = self.get_next();
-                self.direction = Direction::from_step(get_step(&self.current, &self.next));
-                cost = 1_000_000_000;
-            }
+    (sources, doors)
+}
+
+#[cfg(feature = "image")]
+const SCALE: u32 = 4;
+const FRAMES: usize = 200;
+const FRAME_MS: u64 = 50;
+
+// Stands in for the droid on an already discovered map.
+struct Replay<'a> {
+    map: &'a HashMap<Coordinate, (Tile, Cost)>,
+    position: Coordinate,
+}
+
+impl<'a> Area for Replay<'a> {
+    fn step(&mut self, direction: Direction) -> Tile {
+        let next = self.position.step(direction.to_step());
+        let tile = self.map.get(&next).map_or(Tile::Wall, |v| v.0);
+        if tile != Tile::Wall {
+            self.position = next;
         }
-        if !self.map.contains_key(&new_coord) {
-            self.map.insert(new_coord, (tile, cost));
+        tile
+    }
+}
+
+// Keeps every probed cell along with where the droid was afterwards.
+struct Recorder<A: Area> {
+    area: A,
+    position: Coordinate,
+    log: Vec<(Coordinate, Tile, Coordinate)>,
+}
+
+impl<A: Area> Area for Recorder<A> {
+    fn step(&mut self, direction: Direction) -> Tile {
+        let tile = self.area.step(direction);
+        let next = self.position.step(direction.to_step());
+        if tile != Tile::Wall {
+            self.position = next;
         }
+        self.log.push((next, tile, self.position));
+        tile
     }
 }
 
-#[allow(dead_code)]
-fn draw(map: &HashMap<Coordinate, (Tile, Cost)>) {
-    let mut canvas = [[' '; 50]; 50];
-    let c = 25;
-    for (k, v) in map {
-        let x = k.x + c;
-        let y = c - k.y;
-        canvas[y as usize][x as usize] = match v.0 {
-            Tile::Wall => 'X',
-            Tile::Empty => 'o',
-            Tile::Found => '@',
+struct Frame {
+    tiles: HashMap<Coordinate, Tile>,
+    oxygen: HashSet<Coordinate>,
+    droid: Option<Coordinate>,
+}
+
+impl Frame {
+    fn still(map: &HashMap<Coordinate, (Tile, Cost)>) -> Self {
+        Frame {
+            tiles: map.iter().map(|(k, v)| (*k, v.0)).collect(),
+            oxygen: HashSet::new(),
+            droid: Some(Coordinate::new(0, 0)),
+        }
+    }
+    fn glyph(&self, c: &Coordinate) -> char {
+        if self.droid == Some(*c) {
+            'D'
+        } else if self.oxygen.contains(c) {
+            'O'
+        } else {
+            self.tiles.get(c).map_or(' ', |t| t.glyph())
+        }
+    }
+    #[cfg(feature = "image")]
+    fn color(&self, c: &Coordinate) -> image::Rgba<u8> {
+        let rgb = match self.glyph(c) {
+            'D' => [220, 40, 40],
+            'O' => [80, 160, 255],
+            'X' => [90, 90, 90],
+            'o' => [240, 240, 240],
+            '@' => [40, 200, 80],
+            _ => [0, 0, 0],
         };
+        image::Rgba([rgb[0], rgb[1], rgb[2], 255])
     }
-    for row in canvas.iter() {
-        for c in row.iter() {
-            print!("{}", c);
+    fn to_text(&self, (min, max): (Coordinate, Coordinate)) -> String {
+        let mut result = String::new();
+        for y in (min.y..=max.y).rev() {
+            for x in min.x..=max.x {
+                result.push(self.glyph(&Coordinate::new(x, y)));
+            }
+            result.push('\n');
         }
-        println!("");
+        result
+    }
+    #[cfg(feature = "image")]
+    fn to_image(&self, (min, max): (Coordinate, Coordinate)) -> image::RgbaImage {
+        let w = (max.x - min.x + 1) as u32;
+        let h = (max.y - min.y + 1) as u32;
+        image::ImageBuffer::from_fn(w * SCALE, h * SCALE, |x, y| {
+            let c = Coordinate::new(min.x + (x / SCALE) as i128, max.y - (y / SCALE) as i128);
+            self.color(&c)
+        })
     }
 }
 
-fn second() -> u64 {
-    let input = fs::read_to_string(env::args().nth(1).unwrap()).unwrap();
-    let memory = parse(&input);
-    let droid = Droid2::new();
-    let mut intcode = IntCode::new(memory, droid);
-    intcode.run();
-    intcode.bus.get_time().unwrap()
+fn bounds(map: &HashMap<Coordinate, (Tile, Cost)>) -> (Coordinate, Coordinate) {
+    let mut min = Coordinate::new(0, 0);
+    let mut max = Coordinate::new(0, 0);
+    for k in map.keys() {
+        min = Coordinate::new(min.x.min(k.x), min.y.min(k.y));
+        max = Coordinate::new(max.x.max(k.x), max.y.max(k.y));
+    }
+    (min, max)
 }
 
-fn first() -> u64 {
-    let input = fs::read_to_string(env::args().nth(1).unwrap()).unwrap();
-    let memory = parse(&input);
-    let droid = Droid1::new();
-    let mut intcode = IntCode::new(memory, droid);
-    intcode.run();
-    intcode
-        .bus
-        .map
+fn exploration(map: &HashMap<Coordinate, (Tile, Cost)>) -> Vec<Frame> {
+    let origin = Coordinate::new(0, 0);
+    let mut recorder = Recorder {
+        area: Replay {
+            map,
+            position: origin,
+        },
+        position: origin,
+        log: Vec::new(),
+    };
+    explore(&mut recorder);
+    let stride = (recorder.log.len() / FRAMES).max(1);
+    let mut tiles = HashMap::new();
+    tiles.insert(origin, Tile::Empty);
+    let mut frames = Vec::new();
+    for (i, (c, tile, droid)) in recorder.log.iter().enumerate() {
+        tiles.insert(*c, *tile);
+        if i % stride == 0 || i + 1 == recorder.log.len() {
+            frames.push(Frame {
+                tiles: tiles.clone(),
+                oxygen: HashSet::new(),
+                droid: Some(*droid),
+            });
+        }
+    }
+    frames
+}
+
+fn oxygen_fill(map: &HashMap<Coordinate, (Tile, Cost)>, flood: &Flood<Coordinate>) -> Vec<Frame> {
+    let mut oxygen = HashSet::new();
+    flood
+        .timeline
         .iter()
-        .filter_map(|(_, v)| {
-            if let Tile::Found = v.0 {
-                Some(v.1)
-            } else {
-                None
+        .map(|cells| {
+            oxygen.extend(cells.iter().copied());
+            Frame {
+                tiles: map.iter().map(|(k, v)| (*k, v.0)).collect(),
+                oxygen: oxygen.clone(),
+                droid: None,
             }
         })
-        .next()
-        .unwrap()
+        .collect()
+}
+
+fn frames(map: &HashMap<Coordinate, (Tile, Cost)>) -> Vec<Frame> {
+    let mut frames = exploration(map);
+    if let Some(os) = find(map, Tile::Found) {
+        let flood = oxygen(map, &[(os, 0)], &HashMap::new());
+        frames.append(&mut oxygen_fill(map, &flood));
+    }
+    frames
+}
+
+fn watch(map: &HashMap<Coordinate, (Tile, Cost)>) {
+    let b = bounds(map);
+    for frame in frames(map) {
+        print!("\x1b[2J\x1b[H{}", frame.to_text(b));
+        thread::sleep(Duration::from_millis(FRAME_MS));
+    }
+}
+
+#[cfg(feature = "image")]
+fn animate(map: &HashMap<Coordinate, (Tile, Cost)>, filename: &str) {
+    let b = bounds(map);
+    let file = fs::File::create(filename).unwrap();
+    let mut encoder = GifEncoder::new(file);
+    encoder.set_repeat(Repeat::Infinite).unwrap();
+    let delay = image::Delay::from_numer_denom_ms(FRAME_MS as u32, 1);
+    for frame in frames(map) {
+        encoder
+            .encode_frame(image::Frame::from_parts(frame.to_image(b), 0, 0, delay))
+            .unwrap();
+    }
+}
+
+#[cfg(feature = "image")]
+fn png(map: &HashMap<Coordinate, (Tile, Cost)>, filename: &str) {
+    Frame::still(map)
+        .to_image(bounds(map))
+        .save(filename)
+        .unwrap();
+}
+
+#[cfg(not(feature = "image"))]
+fn animate(_: &HashMap<Coordinate, (Tile, Cost)>, _: &str) {
+    panic!("built without the image feature");
+}
+
+#[cfg(not(feature = "image"))]
+fn png(_: &HashMap<Coordinate, (Tile, Cost)>, _: &str) {
+    panic!("built without the image feature");
+}
+
+fn draw(map: &HashMap<Coordinate, (Tile, Cost)>) {
+    print!("{}", to_text(map));
+}
+
+// Saved maps use the glyphs of `draw`, with the droid's starting cell marked
+// as 'D' so that costs can be recomputed on load.
+fn to_text(map: &HashMap<Coordinate, (Tile, Cost)>) -> String {
+    Frame::still(map).to_text(bounds(map))
+}
+
+fn from_text(text: &str) -> HashMap<Coordinate, (Tile, Cost)> {
+    let mut tiles = HashMap::new();
+    let mut origin = None;
+    for (row, line) in text.lines().enumerate() {
+        for (col, ch) in line.chars().enumerate() {
+            let c = Coordinate::new(col as i128, -(row as i128));
+            if ch == 'D' {
+                origin = Some(c);
+            }
+            if let Some(tile) = Tile::from_glyph(ch) {
+                tiles.insert(c, tile);
+            }
+        }
+    }
+    let origin = origin.expect("no starting cell in map");
+    let tiles = tiles
+        .into_iter()
+        .map(|(k, v)| (Coordinate::new(k.x - origin.x, k.y - origin.y), v))
+        .collect();
+    with_costs(&tiles, Coordinate::new(0, 0))
+}
+
+#[cfg(feature = "json")]
+fn to_json(map: &HashMap<Coordinate, (Tile, Cost)>) -> String {
+    let cells: Vec<Value> = map
+        .iter()
+        .map(|(k, v)| json!({"x": k.x as i64, "y": k.y as i64, "tile": format!("{:?}", v.0), "cost": v.1}))
+        .collect();
+    json!({ "cells": cells }).to_string()
+}
+
+#[cfg(feature = "json")]
+fn from_json(text: &str) -> HashMap<Coordinate, (Tile, Cost)> {
+    let value: Value = serde_json::from_str(text).unwrap();
+    let mut map = HashMap::new();
+    for cell in value["cells"].as_array().unwrap() {
+        let c = Coordinate::new(
+            cell["x"].as_i64().unwrap() as i128,
+            cell["y"].as_i64().unwrap() as i128,
+        );
+        let tile = Tile::from_name(cell["tile"].as_str().unwrap());
+        map.insert(c, (tile, cell["cost"].as_u64().unwrap()));
+    }
+    map
+}
+
+#[cfg(not(feature = "json"))]
+fn to_json(_: &HashMap<Coordinate, (Tile, Cost)>) -> String {
+    panic!("built without the json feature");
+}
+
+#[cfg(not(feature = "json"))]
+fn from_json(_: &str) -> HashMap<Coordinate, (Tile, Cost)> {
+    panic!("built without the json feature");
+}
+
+// The input is either the droid's Intcode program or a map saved by `save`.
+fn load(input: &str) -> HashMap<Coordinate, (Tile, Cost)> {
+    let input = input.trim_end();
+    if input.starts_with('{') {
+        from_json(input)
+    } else if input.starts_with(|c: char| c.is_ascii_digit() || c == '-') {
+        let mut intcode = IntCode::new(parse(input), Remote::new());
+        explore(&mut intcode)
+    } else {
+        from_text(input)
+    }
+}
+
+fn save(map: &HashMap<Coordinate, (Tile, Cost)>, filename: &str) {
+    let contents = if filename.ends_with(".json") {
+        to_json(map)
+    } else {
+        to_text(map)
+    };
+    fs::write(filename, contents).unwrap();
+}
+
+fn second(map: &HashMap<Coordinate, (Tile, Cost)>) -> Cost {
+    fill_time(map, find(map, Tile::Found).unwrap()).unwrap()
+}
+
+fn first(map: &HashMap<Coordinate, (Tile, Cost)>) -> Cost {
+    let path = shortest_path(map, Coordinate::new(0, 0), find(map, Tile::Found).unwrap()).unwrap();
+    path.len() as Cost - 1
 }
 
 fn parse(input: &str) -> Vec<i128> {
@@ -378,8 +685,35 @@ fn parse(input: &str) -> Vec<i128> {
 }
 
 fn main() {
-    println!("first: {}", first());
-    println!("second: {}", second());
+    let input = fs::read_to_string(env::args().nth(1).unwrap()).unwrap();
+    let map = load(&input);
+    match env::args().nth(2).as_deref() {
+        Some("save") => save(&map, &env::args().nth(3).unwrap()),
+        Some("draw") => draw(&map),
+        Some("png") => png(&map, &env::args().nth(3).unwrap()),
+        Some("watch") => watch(&map),
+        Some("animate") => animate(&map, &env::args().nth(3).unwrap()),
+        Some("farthest") => {
+            let (from, _) = parse_flood(&env::args().skip(3).collect::<Vec<_>>());
+            let from = from.first().map_or(Coordinate::new(0, 0), |v| v.0);
+            if let Some((c, cost)) = farthest(&map, from) {
+                println!("{} is {} moves away from {}", c, cost, from);
+            }
+        }
+        Some("flood") => {
+            let (sources, doors) = parse_flood(&env::args().skip(3).collect::<Vec<_>>());
+            let flood = oxygen(&map, &sources, &doors);
+            match (flood.duration(), flood.last()) {
+                (Some(minutes), Some(last)) => {
+                    println!("full after {} minutes at {}", minutes, last)
+                }
+                _ => println!("nothing to fill"),
+            }
+        }
+        _ => {}
+    }
+    println!("first: {}", first(&map));
+    println!("second: {}", second(&map));
 }
 
 enum ParamMode {
@@ -548,13 +882,52 @@ where
         self.ptr = next_i;
         op
     }
+}
 
-    fn run(&mut self) {
-        while self.ptr < self.memory.len() {
-            let op = self.execute();
-            if op == 99 {
-                break;
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A corridor with a door at x = 4, the droid starting at its west end.
+    const CORRIDOR: &str = "XXXXXXXXXXXX
+XDoooooooo@X
+XXXXXXXXXXXX";
+
+    fn corridor_flood(door_opens: Cost) -> Flood<Coordinate> {
+        let map = from_text(CORRIDOR);
+        let sources = [(Coordinate::new(0, 0), 0), (Coordinate::new(9, 0), 2)];
+        let mut doors = HashMap::new();
+        doors.insert(Coordinate::new(4, 0), door_opens);
+        oxygen(&map, &sources, &doors)
+    }
+
+    #[test]
+    fn sources_start_at_their_own_minute() {
+        let flood = corridor_flood(20);
+        let time = |x| flood.times[&Coordinate::new(x, 0)];
+        assert_eq!((0..4).map(time).collect::<Vec<_>>(), vec![0, 1, 2, 3]);
+        assert_eq!((5..10).map(time).collect::<Vec<_>>(), vec![6, 5, 4, 3, 2]);
+        assert_eq!(flood.timeline[3].len(), 2);
+    }
+
+    // both sources stall in front of the door until it opens
+    #[test]
+    fn door_opens_after_the_flood_stalled() {
+        let flood = corridor_flood(20);
+        assert_eq!(flood.duration(), Some(20));
+        assert_eq!(flood.last(), Some(Coordinate::new(4, 0)));
+        assert_eq!(flood.timeline.len(), 21);
+        assert!(flood.timeline[7..20].iter().all(|cells| cells.is_empty()));
+        assert_eq!(flood.timeline[20], vec![Coordinate::new(4, 0)]);
+    }
+
+    // an already open door is just another cell
+    #[test]
+    fn door_open_before_the_flood_arrives() {
+        let flood = corridor_flood(1);
+        assert_eq!(flood.times[&Coordinate::new(4, 0)], 4);
+        assert_eq!(flood.times[&Coordinate::new(5, 0)], 5);
+        assert_eq!(flood.duration(), Some(5));
+        assert_eq!(flood.times.len(), 10);
     }
 }
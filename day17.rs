@@ -1,14 +1,22 @@
+#[cfg(feature = "image")]
+use image::codecs::gif::{GifEncoder, Repeat};
 use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs;
+use std::thread;
+use std::time::Duration;
+
+// What to do with the frames of the continuous video feed.
+enum Feed {
+    Off,
+    Terminal(Duration),
+    Record(Vec<ScaffoldMap>),
+}
 
 struct Robot {
-    map: Vec<Vec<char>>,
-    current_line: Vec<char>,
-    facing: Direction,
-    start: (usize, usize),
-    end: (usize, usize),
-    turns: HashSet<(usize, usize)>,
+    camera: String,
+    frame: String,
+    feed: Feed,
     input: String,
     dust: i64,
 }
@@ -16,79 +24,179 @@ struct Robot {
 impl Robot {
     fn new() -> Self {
         Robot {
-            map: Vec::new(),
-            current_line: Vec::new(),
-            facing: Direction::North,
-            start: (0, 0),
-            end: (0, 0),
-            turns: HashSet::new(),
+            camera: String::new(),
+            frame: String::new(),
+            feed: Feed::Off,
             input: String::new(),
             dust: 0,
         }
     }
-    #[allow(dead_code)]
-    fn print_map(&self) {
-        let m = &self.map;
-        for (y, row) in m.iter().enumerate() {
-            for (x, _) in row.iter().enumerate() {
-                print!("{}", m[y][x]);
+    fn show(&mut self, c: char) {
+        self.frame.push(c);
+        if !self.frame.ends_with("\n\n") {
+            return;
+        }
+        // frames are separated by a blank line, prompts alone parse as empty maps
+        let map = ScaffoldMap::parse(&self.frame);
+        self.frame.clear();
+        if map.cells.is_empty() {
+            return;
+        }
+        match &mut self.feed {
+            Feed::Off => (),
+            Feed::Terminal(delay) => {
+                print!("\x1b[2J\x1b[H{}", map.render(None));
+                thread::sleep(*delay);
             }
-            println!("");
+            Feed::Record(frames) => frames.push(map),
         }
     }
-    fn create_routine(&mut self) -> String {
-        let m = &self.map;
-        for (y, row) in m.iter().enumerate() {
-            for (x, _) in row.iter().enumerate() {
-                if is_end(m, y, x) {
-                    self.end = (y, x);
-                    self.turns.insert((y, x));
+}
+
+const COLORS: [u8; 6] = [31, 32, 34, 33, 35, 36];
+#[cfg(feature = "image")]
+const SCALE: u32 = 4;
+
+type Position = (usize, usize);
+
+// What the camera sees: the scaffold and where the robot starts, facing
+// which way.
+struct ScaffoldMap {
+    cells: Vec<Vec<char>>,
+    start: Position,
+    facing: Direction,
+}
+
+impl ScaffoldMap {
+    fn parse(text: &str) -> Self {
+        let is_row = |l: &&str| !l.is_empty() && l.chars().all(|c| "#.^v<>X".contains(c));
+        let cells: Vec<Vec<char>> = text
+            .lines()
+            .skip_while(|l| !is_row(l))
+            .take_while(is_row)
+            .map(|l| l.chars().collect())
+            .collect();
+        let mut start = (0, 0);
+        let mut facing = Direction::North;
+        for (y, row) in cells.iter().enumerate() {
+            for (x, c) in row.iter().enumerate() {
+                if "^v<>".contains(*c) {
+                    start = (y, x);
+                    facing = Direction::parse(*c);
                 }
-                if is_turn(m, y, x) {
-                    self.turns.insert((y, x));
+            }
+        }
+        ScaffoldMap {
+            cells,
+            start,
+            facing,
+        }
+    }
+    fn is_scaffold(&self, (y, x): Position) -> bool {
+        match self.cells.get(y).and_then(|row| row.get(x)) {
+            Some(c) => is_scaffold(*c),
+            None => false,
+        }
+    }
+    fn neighbor(&self, (y, x): Position, d: Direction) -> Option<Position> {
+        let next = match d {
+            Direction::North => (y.checked_sub(1)?, x),
+            Direction::South => (y + 1, x),
+            Direction::West => (y, x.checked_sub(1)?),
+            Direction::East => (y, x + 1),
+        };
+        if self.is_scaffold(next) {
+            Some(next)
+        } else {
+            None
+        }
+    }
+    fn connections(&self, p: Position) -> Vec<Direction> {
+        [
+            Direction::North,
+            Direction::East,
+            Direction::South,
+            Direction::West,
+        ]
+        .iter()
+        .filter(|d| self.neighbor(p, **d).is_some())
+        .copied()
+        .collect()
+    }
+    fn scaffold(&self) -> Vec<Position> {
+        let mut result = Vec::new();
+        for (y, row) in self.cells.iter().enumerate() {
+            for (x, c) in row.iter().enumerate() {
+                if is_scaffold(*c) {
+                    result.push((y, x));
                 }
             }
         }
+        result
+    }
+    fn intersections(&self) -> Vec<Position> {
+        self.scaffold()
+            .into_iter()
+            .filter(|p| self.connections(*p).len() == 4)
+            .collect()
+    }
+    fn endpoints(&self) -> Vec<Position> {
+        self.scaffold()
+            .into_iter()
+            .filter(|p| self.connections(*p).len() == 1)
+            .collect()
+    }
+    fn turns(&self) -> Vec<Position> {
+        self.scaffold()
+            .into_iter()
+            .filter(|p| {
+                let c = self.connections(*p);
+                c.len() == 2 && c[1] != c[0].turn(&Turn::Right).turn(&Turn::Right)
+            })
+            .collect()
+    }
+    fn alignment(&self) -> usize {
+        self.intersections().iter().map(|(y, x)| y * x).sum()
+    }
+    // Goes straight through every intersection and turns only at corners,
+    // stopping at a dead end or once back where it already turned on a loop.
+    fn path(&self) -> Vec<Movement> {
         let mut current = self.start;
-        let mut patterns_v = Vec::new();
-        while current != self.end {
-            let turn = rotate(m, current.0, current.1, &mut self.facing);
-            let distance = find_next(&mut self.turns, &mut current, self.facing);
-            patterns_v.push(Movement::new(turn, distance));
-        }
-
-        let patterns_string = patterns_v
+        let mut facing = self.facing;
+        let mut result = Vec::new();
+        let mut seen = HashSet::new();
+        while let Some(turn) = [Turn::Left, Turn::Right]
             .iter()
-            .map(|p| p.stringify() + ",")
-            .collect::<String>();
-
-        let mut patterns: HashMap<char, String> = HashMap::new();
-
-        for i in 2..21 {
-            patterns.insert('A', (&patterns_string)[0..i].to_string());
-            if is_valid_pattern(&patterns[&'A'])
-                && find_substring(&patterns_string, &patterns[&'A'])
-            {
-                let remain = remove_pattern_from(&patterns_string, &patterns[&'A']);
-                for j in 2..21 {
-                    patterns.insert('B', (&remain)[0..j].to_string());
-                    if is_valid_pattern(&patterns[&'B'])
-                        && &patterns[&'A'] != &patterns[&'B']
-                        && find_substring(&remain, &patterns[&'B'])
-                    {
-                        let remain = remove_pattern_from(&remain, &patterns[&'B']);
-                        for k in 2..21 {
-                            if k < remain.len() {
-                                patterns.insert('C', (&remain)[0..k].to_string());
-                                if is_valid_pattern(&patterns[&'C'])
-                                    && &patterns[&'A'] != &patterns[&'C']
-                                    && &patterns[&'B'] != &patterns[&'C']
-                                    && find_substring(&remain, &patterns[&'C'])
-                                {
-                                    let remain = remove_pattern_from(&remain, &patterns[&'C']);
-                                    if remain.is_empty() {
-                                        return parse_routine(&patterns_string, &patterns);
-                                    }
+            .find(|t| self.neighbor(current, facing.turn(t)).is_some())
+        {
+            if !seen.insert((current, facing)) {
+                break;
+            }
+            facing = facing.turn(turn);
+            let mut moves = 0;
+            while let Some(next) = self.neighbor(current, facing) {
+                current = next;
+                moves += 1;
+            }
+            result.push(Movement::new(*turn, moves));
+        }
+        result
+    }
+    // Colors every cell by the function that last moved the robot onto it.
+    fn render(&self, routine: Option<&Routine>) -> String {
+        let mut colors = HashMap::new();
+        if let Some(r) = routine {
+            let mut current = self.start;
+            let mut facing = self.facing;
+            for f in r.main.iter() {
+                for t in r.functions[*f].iter() {
+                    match t {
+                        Token::Turn(turn) => facing = facing.turn(turn),
+                        Token::Forward(n) => {
+                            for _ in 0..*n {
+                                if let Some(next) = self.neighbor(current, facing) {
+                                    current = next;
+                                    colors.insert(current, COLORS[*f % COLORS.len()]);
                                 }
                             }
                         }
@@ -96,257 +204,340 @@ impl Robot {
                 }
             }
         }
-        String::new()
-    }
-}
-
-fn parse_routine(patterns: &str, m: &HashMap<char, String>) -> String {
-    let mut result = String::new();
-    let mut head = 0;
-    while head < patterns.len() {
-        let c = m
-            .iter()
-            .filter_map(|(k, v)| {
-                if &&patterns[head..head + v.len()] == v {
-                    Some(k)
-                } else {
-                    None
+        let mut result = String::new();
+        for (y, row) in self.cells.iter().enumerate() {
+            for (x, c) in row.iter().enumerate() {
+                match colors.get(&(y, x)) {
+                    Some(color) => result.push_str(&format!("\x1b[{}m{}\x1b[0m", color, c)),
+                    None => result.push(*c),
                 }
+            }
+            result.push('\n');
+        }
+        result
+    }
+    #[cfg(feature = "image")]
+    fn to_image(&self) -> image::RgbaImage {
+        let h = self.cells.len() as u32;
+        let w = self.cells.iter().map(|row| row.len()).max().unwrap_or(0) as u32;
+        image::ImageBuffer::from_fn(w * SCALE, h * SCALE, |x, y| {
+            let c = self
+                .cells
+                .get((y / SCALE) as usize)
+                .and_then(|row| row.get((x / SCALE) as usize));
+            image::Rgba(match c {
+                Some('#') => [240, 240, 240, 255],
+                Some('^') | Some('v') | Some('<') | Some('>') => [220, 40, 40, 255],
+                Some('X') => [240, 200, 40, 255],
+                _ => [0, 0, 0, 255],
             })
-            .next()
-            .unwrap();
-        head += m[c].len();
-        result.push(*c);
-        result.push(match head < patterns.len() {
-            true => ',',
-            false => '\n',
-        });
-    }
-    result.push_str(
-        &"ABC"
-            .chars()
-            .map(|c| {
-                let mut s = m[&c].to_owned();
-                s.pop();
-                s.push('\n');
-                s
-            })
-            .collect::<String>()
-            .as_str(),
-    );
-    result.push_str("N\n");
-    result
+        })
+    }
 }
 
-fn remove_pattern_from(source: &str, target: &str) -> String {
-    let mut result = String::new();
-    let mut prev_head = 0;
-    let mut i = 0;
-    while i < source.len() {
-        if source[i..i + 1] == target[0..1]
-            && target.len() <= source[i..].len()
-            && target == &source[i..i + target.len()]
-        {
-            result.push_str(&source[prev_head..i]);
-            i += target.len();
-            prev_head = i;
-        } else {
-            i += 1;
+fn plan(map: &ScaffoldMap, compressor: &Compressor) -> Option<Routine> {
+    let path = map.path();
+    // an empty path means the robot already faces along the scaffold
+    if !path.is_empty() {
+        if let Some(r) = compressor.compress(&path).into_iter().next() {
+            return Some(r);
         }
     }
-    if prev_head + target.len() != source.len() {
-        result.push_str(&source[prev_head..]);
-    }
+    // the straight-through path does not fit, try turning at intersections
+    let mut result = None;
+    traversals(map, |tokens| {
+        result = compressor.compress_tokens(tokens).into_iter().next();
+        result.is_some()
+    });
     result
 }
 
-fn is_valid_pattern(s: &str) -> bool {
-    for (i, c) in s.chars().enumerate() {
-        if i == s.len() - 2 && !c.is_digit(10) {
-            return false;
-        }
-        if i == s.len() - 1 && c != ',' {
-            return false;
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+enum Token {
+    Turn(Turn),
+    Forward(Moves),
+}
+
+impl Token {
+    fn stringify(&self) -> String {
+        match *self {
+            Token::Turn(t) => t.stringify(),
+            Token::Forward(n) => n.to_string(),
         }
     }
-    true
 }
 
-fn find_substring(source: &str, substring: &str) -> bool {
-    if source.len() < substring.len() {
-        return false;
+fn stringify(tokens: &[Token]) -> String {
+    tokens
+        .iter()
+        .map(|t| t.stringify())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[derive(Debug, Clone)]
+struct Routine {
+    main: Vec<usize>,
+    functions: Vec<Vec<Token>>,
+}
+
+impl Routine {
+    fn main(&self) -> String {
+        self.main
+            .iter()
+            .map(|f| ((b'A' + *f as u8) as char).to_string())
+            .collect::<Vec<_>>()
+            .join(",")
     }
-    for i in 0..source.len() - substring.len() {
-        let mut found = true;
-        for j in 0..substring.len() {
-            let next = source[(i + j)..(i + j + 1)] == substring[j..j + 1];
-            found &= next;
-            if !found {
-                break;
-            }
+    fn len(&self) -> usize {
+        self.main().len()
+            + self
+                .functions
+                .iter()
+                .map(|f| stringify(f).len())
+                .sum::<usize>()
+    }
+    // The main routine and then every function, one per line.
+    fn to_text(&self) -> String {
+        let mut result = self.main() + "\n";
+        for f in self.functions.iter() {
+            result.push_str(&stringify(f));
+            result.push('\n');
         }
-        if found {
-            return true;
+        result
+    }
+    fn to_input(&self, video: bool) -> String {
+        if self.functions.len() > 3 {
+            panic!("The robot only takes functions A, B and C: {}", self.main());
         }
+        let mut result = self.main() + "\n";
+        for i in 0..3 {
+            // the robot asks for all three functions even when fewer are used
+            match self.functions.get(i) {
+                Some(f) => result.push_str(&stringify(f)),
+                None => result.push('L'),
+            }
+            result.push('\n');
+        }
+        result.push_str(if video { "y\n" } else { "n\n" });
+        result
     }
-    false
 }
 
-fn rotate(m: &Vec<Vec<char>>, y: usize, x: usize, direction: &mut Direction) -> Turn {
-    let turn = match direction {
-        Direction::North => {
-            if x > 0 && m[y][x - 1] == '#' {
-                Turn::Left
-            } else {
-                Turn::Right
-            }
+// Position in the token sequence: the token index and how much of it, if it
+// is a forward move, has already been covered.
+type Cursor = (usize, Moves);
+
+// Splits a path into a main routine calling at most `functions` functions,
+// none of them longer than `limit` characters. When the path cannot be
+// covered otherwise, forward moves may be split in two so that the halves
+// end up in different functions.
+struct Compressor {
+    functions: usize,
+    limit: usize,
+}
+
+impl Compressor {
+    // The limits of the vacuum robot's movement functions.
+    fn new() -> Self {
+        Compressor::with(3, 20)
+    }
+
+    fn with(functions: usize, limit: usize) -> Self {
+        Compressor { functions, limit }
+    }
+
+    fn compress(&self, movements: &[Movement]) -> Vec<Routine> {
+        let tokens = movements
+            .iter()
+            .flat_map(|m| vec![Token::Turn(m.turn), Token::Forward(m.moves)])
+            .collect::<Vec<_>>();
+        self.compress_tokens(&tokens)
+    }
+
+    // Every routine that fits, shortest main routine first.
+    fn compress_tokens(&self, tokens: &[Token]) -> Vec<Routine> {
+        let mut routine = Routine {
+            main: Vec::new(),
+            functions: Vec::new(),
+        };
+        let mut results = Vec::new();
+        self.search(tokens, (0, 0), false, &mut routine, &mut results);
+        if results.is_empty() {
+            self.search(tokens, (0, 0), true, &mut routine, &mut results);
+        }
+        results.sort_by_key(|r| (r.main.len(), r.len()));
+        results
+    }
+
+    fn search(
+        &self,
+        tokens: &[Token],
+        at: Cursor,
+        split: bool,
+        routine: &mut Routine,
+        results: &mut Vec<Routine>,
+    ) {
+        if at.0 == tokens.len() {
+            results.push(routine.clone());
+            return;
+        }
+        if 2 * routine.main.len() + 1 > self.limit {
+            return;
         }
-        Direction::South => {
-            if x > 0 && m[y][x - 1] == '#' {
-                Turn::Right
-            } else {
-                Turn::Left
+        for f in 0..routine.functions.len() {
+            if let Some(next) = consume(tokens, at, &routine.functions[f]) {
+                routine.main.push(f);
+                self.search(tokens, next, split, routine, results);
+                routine.main.pop();
             }
         }
-        Direction::West => {
-            if y > 0 && m[y - 1][x] == '#' {
-                Turn::Right
-            } else {
-                Turn::Left
+        if routine.functions.len() == self.functions {
+            return;
+        }
+        for body in self.candidates(tokens, at, split) {
+            if routine.functions.contains(&body) {
+                continue;
             }
+            let next = consume(tokens, at, &body).unwrap();
+            routine.main.push(routine.functions.len());
+            routine.functions.push(body);
+            self.search(tokens, next, split, routine, results);
+            routine.functions.pop();
+            routine.main.pop();
         }
-        Direction::East => {
-            if y > 0 && m[y - 1][x] == '#' {
-                Turn::Left
-            } else {
-                Turn::Right
+    }
+
+    // Function bodies that can start at `at`, either ending on a token
+    // boundary or partway through a forward move.
+    fn candidates(&self, tokens: &[Token], at: Cursor, split: bool) -> Vec<Vec<Token>> {
+        let mut result = Vec::new();
+        let mut body = Vec::new();
+        let (mut i, mut used) = at;
+        while i < tokens.len() {
+            match tokens[i] {
+                Token::Turn(t) => body.push(Token::Turn(t)),
+                Token::Forward(n) => {
+                    for k in (1..n - used).filter(|_| split) {
+                        let mut split = body.clone();
+                        split.push(Token::Forward(k));
+                        if stringify(&split).len() <= self.limit {
+                            result.push(split);
+                        }
+                    }
+                    body.push(Token::Forward(n - used));
+                    used = 0;
+                }
             }
+            i += 1;
+            if stringify(&body).len() > self.limit {
+                break;
+            }
+            result.push(body.clone());
         }
-    };
-    *direction = direction.turn(&turn);
-    turn
-}
-
-#[allow(unused_assignments)]
-fn find_next(
-    turns: &mut HashSet<(usize, usize)>,
-    current: &mut (usize, usize),
-    direction: Direction,
-) -> u64 {
-    let mut next = *current;
-    let result = match direction {
-        Direction::North => {
-            next = *turns
-                .iter()
-                .filter(|(y, x)| x == &current.1 && y < &current.0)
-                .max_by(|x, y| x.0.cmp(&y.0))
-                .unwrap();
-            current.0 - next.0
+        result
+    }
+}
+
+fn consume(tokens: &[Token], at: Cursor, body: &[Token]) -> Option<Cursor> {
+    let (mut i, mut used) = at;
+    for t in body {
+        match (t, tokens.get(i)) {
+            (Token::Turn(a), Some(Token::Turn(b))) if a == b => i += 1,
+            (Token::Forward(k), Some(Token::Forward(n))) if used + k <= *n => {
+                used += k;
+                if used == *n {
+                    i += 1;
+                    used = 0;
+                }
+            }
+            _ => return None,
         }
-        Direction::South => {
-            next = *turns
-                .iter()
-                .filter(|(y, x)| x == &current.1 && y > &current.0)
-                .min_by(|x, y| x.0.cmp(&y.0))
-                .unwrap();
-            next.0 - current.0
+    }
+    Some((i, used))
+}
+
+fn is_scaffold(c: char) -> bool {
+    matches!(c, '#' | '^' | 'v' | '<' | '>')
+}
+
+type Edge = (Position, Position);
+
+fn edge(a: Position, b: Position) -> Edge {
+    if a < b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+// Walks over the scaffold that use every stretch between two neighboring
+// cells exactly once, turning or going straight at intersections. `f` gets
+// the moves of each walk, straight-through ones first, and returns true to
+// stop the enumeration.
+fn traversals<F>(map: &ScaffoldMap, mut f: F)
+where
+    F: FnMut(&[Token]) -> bool,
+{
+    let total = map
+        .scaffold()
+        .into_iter()
+        .map(|p| map.connections(p).len())
+        .sum::<usize>()
+        / 2;
+    let mut used = HashSet::new();
+    walk(map, map.start, map.facing, &[], &mut used, total, &mut f);
+}
+
+fn walk<F>(
+    map: &ScaffoldMap,
+    current: Position,
+    facing: Direction,
+    tokens: &[Token],
+    used: &mut HashSet<Edge>,
+    total: usize,
+    f: &mut F,
+) -> bool
+where
+    F: FnMut(&[Token]) -> bool,
+{
+    if used.len() == total {
+        return f(tokens);
+    }
+    let options = [
+        (facing, vec![]),
+        (facing.turn(&Turn::Left), vec![Turn::Left]),
+        (facing.turn(&Turn::Right), vec![Turn::Right]),
+        (
+            facing.turn(&Turn::Right).turn(&Turn::Right),
+            vec![Turn::Right, Turn::Right],
+        ),
+    ];
+    for (d, turns) in options.iter() {
+        // turning around is only possible before the first step
+        if turns.len() == 2 && !tokens.is_empty() {
+            continue;
         }
-        Direction::West => {
-            next = *turns
-                .iter()
-                .filter(|(y, x)| y == &current.0 && x < &current.1)
-                .max_by(|x, y| x.1.cmp(&y.1))
-                .unwrap();
-            current.1 - next.1
+        let next = match map.neighbor(current, *d) {
+            Some(next) if !used.contains(&edge(current, next)) => next,
+            _ => continue,
+        };
+        let mut tokens = tokens.to_vec();
+        match tokens.last_mut() {
+            Some(Token::Forward(n)) if turns.is_empty() => *n += 1,
+            _ => {
+                tokens.extend(turns.iter().map(|t| Token::Turn(*t)));
+                tokens.push(Token::Forward(1));
+            }
         }
-        Direction::East => {
-            next = *turns
-                .iter()
-                .filter(|(y, x)| y == &current.0 && x > &current.1)
-                .min_by(|x, y| x.1.cmp(&y.1))
-                .unwrap();
-            next.1 - current.1
+        used.insert(edge(current, next));
+        let done = walk(map, next, *d, &tokens, used, total, f);
+        used.remove(&edge(current, next));
+        if done {
+            return true;
         }
-    };
-    *current = turns.take(&next).unwrap();
-    result as u64
-}
-
-fn is_turn(m: &Vec<Vec<char>>, y: usize, x: usize) -> bool {
-    !is_intersection(m, y, x)
-        && (is_upper_right(m, y, x)
-            || is_bottom_right(m, y, x)
-            || is_upper_left(m, y, x)
-            || is_bottom_left(m, y, x))
-}
-
-fn is_end(m: &Vec<Vec<char>>, y: usize, x: usize) -> bool {
-    let height = m.len();
-    let width = m[0].len();
-    x > 0
-        && x < width - 2
-        && y > 0
-        && y < height - 2
-        && m[y][x] == '#'
-        && [m[y + 1][x], m[y - 1][x], m[y][x + 1], m[y][x - 1]]
-            .iter()
-            .filter(|&&c| c == '#')
-            .count()
-            == 1
-}
-
-fn is_intersection(m: &Vec<Vec<char>>, y: usize, x: usize) -> bool {
-    let height = m.len();
-    let width = m[0].len();
-    x > 0
-        && x < width - 2
-        && y > 0
-        && y < height - 2
-        && m[y][x] == '#'
-        && m[y + 1][x] == '#'
-        && m[y - 1][x] == '#'
-        && m[y][x + 1] == '#'
-        && m[y][x - 1] == '#'
-}
-
-fn is_upper_right(m: &Vec<Vec<char>>, y: usize, x: usize) -> bool {
-    let height = m.len();
-    !is_intersection(m, y, x)
-        && y < height - 2
-        && x > 0
-        && m[y][x] == '#'
-        && m[y + 1][x] == '#'
-        && m[y][x - 1] == '#'
-}
-
-fn is_bottom_right(m: &Vec<Vec<char>>, y: usize, x: usize) -> bool {
-    !is_intersection(m, y, x)
-        && y > 0
-        && x > 0
-        && m[y][x] == '#'
-        && m[y - 1][x] == '#'
-        && m[y][x - 1] == '#'
-}
-
-fn is_upper_left(m: &Vec<Vec<char>>, y: usize, x: usize) -> bool {
-    let height = m.len();
-    let width = m[0].len();
-    !is_intersection(m, y, x)
-        && y < height - 2
-        && x < width - 2
-        && m[y][x] == '#'
-        && m[y + 1][x] == '#'
-        && m[y][x + 1] == '#'
-}
-
-fn is_bottom_left(m: &Vec<Vec<char>>, y: usize, x: usize) -> bool {
-    let width = m[0].len();
-    !is_intersection(m, y, x)
-        && y > 0
-        && x < width - 2
-        && m[y][x] == '#'
-        && m[y - 1][x] == '#'
-        && m[y][x + 1] == '#'
+    }
+    false
 }
 
 impl Bus for Robot {
@@ -356,24 +547,11 @@ impl Bus for Robot {
         result
     }
     fn output(&mut self, v: i64) {
-        let c = (v as u8) as char;
-        match c {
-            '#' | '.' | '^' | 'v' | '<' | '>' => {
-                match c {
-                    '^' | '>' | 'v' | '<' => {
-                        self.facing = Direction::parse(c);
-                        self.start = (self.map.len() as usize, self.current_line.len() as usize);
-                    }
-                    _ => (),
-                };
-                self.current_line.push(c);
-            }
-            '\n' => {
-                self.map.push(self.current_line.clone());
-                self.current_line.clear();
-            }
+        match (v, &self.feed) {
+            (0..=127, Feed::Off) => self.camera.push((v as u8) as char),
+            (0..=127, _) => self.show((v as u8) as char),
             _ => self.dust = v,
-        };
+        }
     }
 }
 
@@ -392,7 +570,7 @@ impl Turn {
     }
 }
 
-#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Hash)]
 enum Direction {
     North,
     East,
@@ -440,60 +618,111 @@ impl Movement {
     fn new(turn: Turn, moves: Moves) -> Self {
         Movement { turn, moves }
     }
-    fn stringify(&self) -> String {
-        self.turn.stringify() + "," + &self.moves.to_string()
-    }
 }
 
-fn find_routine() -> String {
-    let input = fs::read_to_string(env::args().nth(1).unwrap()).unwrap();
-    let memory = parse(&input);
-    let robot = Robot::new();
-    let mut intcode = IntCode::new(memory, robot);
+fn camera(memory: Vec<i64>) -> ScaffoldMap {
+    let mut intcode = IntCode::new(memory, Robot::new());
     intcode.run();
-    intcode.bus.create_routine()
+    ScaffoldMap::parse(&intcode.bus.camera)
 }
-fn second() -> u64 {
-    let input = fs::read_to_string(env::args().nth(1).unwrap()).unwrap();
-    let mut memory = parse(&input);
+
+fn run_routine(mut memory: Vec<i64>, routine: &Routine, feed: Feed) -> Robot {
     memory[0] = 2;
-    let robot = Robot::new();
+    let mut robot = Robot::new();
+    robot.input = routine.to_input(!matches!(feed, Feed::Off));
+    robot.feed = feed;
     let mut intcode = IntCode::new(memory, robot);
-    intcode.bus.input = find_routine();
     intcode.run();
-    intcode.bus.dust as u64
+    intcode.bus
 }
 
-fn first() -> u64 {
-    let input = fs::read_to_string(env::args().nth(1).unwrap()).unwrap();
-    let memory = parse(&input);
-    let robot = Robot::new();
-    let mut intcode = IntCode::new(memory, robot);
-    intcode.run();
-    let m = &intcode.bus.map;
-    let mut result = 0;
-    for (y, row) in m.iter().enumerate() {
-        let height = m.len();
-        let width = row.len();
-        for (x, _) in row.iter().enumerate() {
-            if x > 0 && x < width - 2 && y > 0 && y < height - 2 {
-                if m[y][x] == '#'
-                    && m[y + 1][x] == '#'
-                    && m[y - 1][x] == '#'
-                    && m[y][x + 1] == '#'
-                    && m[y][x - 1] == '#'
-                {
-                    result += y * x;
-                }
-            }
-        }
+fn second(memory: Vec<i64>, routine: &Routine) -> u64 {
+    run_routine(memory, routine, Feed::Off).dust as u64
+}
+
+#[cfg(feature = "image")]
+fn animate(frames: &[ScaffoldMap], filename: &str, fps: u64) {
+    let file = fs::File::create(filename).unwrap();
+    let mut encoder = GifEncoder::new(file);
+    encoder.set_repeat(Repeat::Infinite).unwrap();
+    let delay = image::Delay::from_numer_denom_ms(1000, fps as u32);
+    for frame in frames {
+        encoder
+            .encode_frame(image::Frame::from_parts(frame.to_image(), 0, 0, delay))
+            .unwrap();
     }
-    result as u64
+}
+
+#[cfg(not(feature = "image"))]
+fn animate(_: &[ScaffoldMap], _: &str, _: u64) {
+    panic!("built without the image feature");
+}
+
+fn first(map: &ScaffoldMap) -> u64 {
+    map.alignment() as u64
 }
 
 fn main() {
-    println!("first: {}", first());
-    println!("second: {}", second());
+    let input = fs::read_to_string(env::args().nth(1).unwrap()).unwrap();
+    // either the camera program or a map copied from the puzzle text
+    let program = input.starts_with(|c: char| c.is_ascii_digit());
+    let map = if program {
+        camera(parse(&input))
+    } else {
+        ScaffoldMap::parse(&input)
+    };
+    println!("first: {}", first(&map));
+    let mode = env::args().nth(2);
+    // other limits only make sense for maps, the robot itself takes 3 of 20
+    let custom = mode.as_deref() == Some("compress");
+    let compressor = if custom {
+        let arg = |n| env::args().nth(n).unwrap().parse::<usize>().unwrap();
+        Compressor::with(arg(3), arg(4))
+    } else {
+        Compressor::new()
+    };
+    let routine = plan(&map, &compressor);
+    if !program || custom || mode.as_deref() == Some("draw") {
+        println!(
+            "intersections: {:?}\nendpoints: {:?}\nturns: {}",
+            map.intersections(),
+            map.endpoints(),
+            map.turns().len()
+        );
+        print!("{}", map.render(routine.as_ref()));
+        if let Some(r) = &routine {
+            print!("{}", r.to_text());
+        }
+    }
+    let fps = |n| {
+        let fps = env::args()
+            .nth(n)
+            .map_or(10, |f: String| f.parse::<u64>().unwrap());
+        if fps == 0 {
+            panic!("Invalid frame rate: {}", fps);
+        }
+        fps
+    };
+    match routine {
+        Some(r) if program && mode.as_deref() == Some("video") => {
+            let delay = Duration::from_millis(1000 / fps(3));
+            let robot = run_routine(parse(&input), &r, Feed::Terminal(delay));
+            println!("second: {}", robot.dust);
+        }
+        Some(r) if program && mode.as_deref() == Some("record") => {
+            let robot = run_routine(parse(&input), &r, Feed::Record(Vec::new()));
+            if let Feed::Record(frames) = &robot.feed {
+                animate(frames, &env::args().nth(3).unwrap(), fps(4));
+            }
+            println!("second: {}", robot.dust);
+        }
+        Some(r) if program && !custom => println!("second: {}", second(parse(&input), &r)),
+        Some(_) => (),
+        None => println!(
+            "second: no routine fits in {} functions of {} characters",
+            compressor.functions, compressor.limit
+        ),
+    }
 }
 
 fn parse(input: &str) -> Vec<i64> {
@@ -677,3 +906,159 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The sample maps from the puzzle: the camera's view, with four
+    // intersections, and the one used to explain movement functions.
+    const CAMERA: &str = "..#..........
+..#..........
+#######...###
+#.#...#...#.#
+#############
+..#...#...#..
+..#####...^..";
+
+    const MOVEMENT: &str = "#######...#####
+#.....#...#...#
+#.....#...#...#
+......#...#...#
+......#...###.#
+......#.....#.#
+^########...#.#
+......#.#...#.#
+......#########
+........#...#..
+....#########..
+....#...#......
+....#...#......
+....#...#......
+....#####......";
+
+    // The path through the second sample map.
+    const SAMPLE_PATH: &str = "R,8,R,8,R,4,R,4,R,8,L,6,L,2,R,4,R,4,R,8,R,8,R,8,L,6,L,2";
+
+    fn stringify_path(path: &[Movement]) -> String {
+        path.iter()
+            .map(|m| format!("{},{}", m.turn.stringify(), m.moves))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    fn tokens(path: &str) -> Vec<Token> {
+        path.split(',')
+            .map(|t| match t {
+                "L" => Token::Turn(Turn::Left),
+                "R" => Token::Turn(Turn::Right),
+                n => Token::Forward(n.parse().unwrap()),
+            })
+            .collect()
+    }
+
+    // The path a routine drives, with forward moves split across functions
+    // joined again.
+    fn expand(routine: &Routine) -> Vec<Token> {
+        let mut result: Vec<Token> = Vec::new();
+        for f in routine.main.iter() {
+            for t in routine.functions[*f].iter() {
+                match (result.last_mut(), t) {
+                    (Some(Token::Forward(n)), Token::Forward(k)) => *n += k,
+                    _ => result.push(*t),
+                }
+            }
+        }
+        result
+    }
+
+    fn fits(routine: &Routine, compressor: &Compressor) -> bool {
+        routine.main().len() <= compressor.limit
+            && routine.functions.len() <= compressor.functions
+            && routine
+                .functions
+                .iter()
+                .all(|f| stringify(f).len() <= compressor.limit)
+    }
+
+    #[test]
+    fn sample_path() {
+        let compressor = Compressor::new();
+        let path = tokens(SAMPLE_PATH);
+        let results = compressor.compress_tokens(&path);
+        for r in results.iter() {
+            assert_eq!(expand(r), path, "{}", r.to_text());
+            assert!(fits(r, &compressor), "{}", r.to_text());
+        }
+        // the split given in the puzzle
+        let puzzle = "A,B,C,B,A,C\nR,8,R,8\nR,4,R,4,R,8\nL,6,L,2\n";
+        assert!(results.iter().any(|r| r.to_text() == puzzle));
+    }
+
+    // routines come fewest calls first: the sample is too long for two
+    // functions of 20 characters, and a repeated pattern needs only one
+    #[test]
+    fn fewest_calls_first() {
+        let results = Compressor::new().compress_tokens(&tokens(SAMPLE_PATH));
+        assert_eq!(results[0].main.len(), 3);
+        let results = Compressor::new().compress_tokens(&tokens("R,4,R,4,R,4,R,4"));
+        assert_eq!(results[0].main, vec![0]);
+        assert_eq!(results[0].to_text(), "A\nR,4,R,4,R,4,R,4\n");
+    }
+
+    // "R,12" is too long for a function, so the move has to be split
+    #[test]
+    fn split_forward_move() {
+        let compressor = Compressor::with(3, 3);
+        let path = tokens("R,12");
+        let results = compressor.compress_tokens(&path);
+        assert!(!results.is_empty());
+        for r in results.iter() {
+            assert_eq!(expand(r), path, "{}", r.to_text());
+            assert!(fits(r, &compressor), "{}", r.to_text());
+            assert!(r.functions.len() > 1, "{}", r.to_text());
+        }
+    }
+
+    #[test]
+    fn no_routine_fits() {
+        let path = tokens("R,1,L,2,R,3,L,4");
+        assert!(Compressor::with(2, 5).compress_tokens(&path).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "only takes functions A, B and C")]
+    fn robot_takes_three_functions() {
+        let routine = Routine {
+            main: vec![0, 1, 2, 3],
+            functions: vec![tokens("R,1"), tokens("L,2"), tokens("R,3"), tokens("L,4")],
+        };
+        routine.to_input(false);
+    }
+
+    #[test]
+    fn camera_sample() {
+        let map = ScaffoldMap::parse(CAMERA);
+        assert_eq!(map.intersections(), vec![(2, 2), (4, 2), (4, 6), (4, 10)]);
+        assert_eq!(map.alignment(), 76);
+        assert_eq!(map.start, (6, 10));
+        assert_eq!(map.facing, Direction::North);
+    }
+
+    #[test]
+    fn movement_sample() {
+        let map = ScaffoldMap::parse(MOVEMENT);
+        assert_eq!(map.intersections(), vec![(6, 6), (8, 8), (8, 12), (10, 8)]);
+        assert_eq!(stringify_path(&map.path()), SAMPLE_PATH);
+        let routine = plan(&map, &Compressor::new()).unwrap();
+        assert_eq!(expand(&routine), tokens(SAMPLE_PATH));
+        assert_eq!(routine.main(), "A,B,C");
+    }
+
+    // a scaffold without dead ends would otherwise be followed forever
+    #[test]
+    fn path_around_a_loop() {
+        let map = ScaffoldMap::parse("#####\n#...#\n#...#\n#...#\n^####");
+        assert_eq!(stringify_path(&map.path()), "R,4,L,4,L,4,L,4,L,4");
+    }
+}
@@ -1,44 +1,27 @@
-// use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use std::env;
 use std::fs;
 
-enum Axis {
-    X,
-    Y,
-}
-
-struct Scanner {
-    counter: u64,
-    limit: u64,
-    axis: Axis,
+struct Drone {
+    coordinates: [u64; 2],
+    next: usize,
     output: u64,
 }
 
-impl Scanner {
-    fn new(counter: u64, limit: u64) -> Self {
+impl Drone {
+    fn new(x: u64, y: u64) -> Self {
         Self {
-            counter,
-            limit,
-            axis: Axis::X,
+            coordinates: [x, y],
+            next: 0,
             output: 0,
         }
     }
 }
 
-impl Bus for Scanner {
-    #[allow(unused_assignments)]
+impl Bus for Drone {
     fn input(&mut self) -> i64 {
-        let mut result = 0;
-        match self.axis {
-            Axis::X => {
-                self.axis = Axis::Y;
-                result = self.counter % self.limit;
-            }
-            Axis::Y => {
-                self.axis = Axis::X;
-                result = self.counter / self.limit;
-            }
-        };
+        let result = self.coordinates[self.next];
+        self.next = (self.next + 1) % 2;
         result as i64
     }
     fn output(&mut self, v: i64) {
@@ -46,51 +29,176 @@ impl Bus for Scanner {
     }
 }
 
-fn first() -> u64 {
-    let input = fs::read_to_string(env::args().nth(1).unwrap()).unwrap();
-    let memory = parse(&input);
-    let mut grid = vec![vec![0; 50]; 50];
-    for i in 0..2500 {
-        let scanner = Scanner::new(i as u64, 50);
-        let mut intcode = IntCode::new(&memory, scanner);
+// Answers whether a point is pulled by the beam, deploying a drone only for
+// points it has not been asked about before.
+struct Oracle {
+    memory: Vec<i64>,
+    cache: HashMap<(u64, u64), bool>,
+    runs: u64,
+}
+
+impl Oracle {
+    fn new(memory: Vec<i64>) -> Self {
+        Oracle {
+            memory,
+            cache: HashMap::new(),
+            runs: 0,
+        }
+    }
+    fn probe(&mut self, x: u64, y: u64) -> bool {
+        if let Some(pulled) = self.cache.get(&(x, y)) {
+            return *pulled;
+        }
+        let mut intcode = IntCode::new(&self.memory, Drone::new(x, y));
         intcode.run();
-        grid[i / 50][i % 50] = intcode.bus.output;
+        self.runs += 1;
+        let pulled = intcode.bus.output == 1;
+        self.cache.insert((x, y), pulled);
+        pulled
+    }
+    fn size(&self) -> (u64, u64) {
+        let w = self.cache.keys().map(|k| k.0 + 1).max().unwrap_or(0);
+        let h = self.cache.keys().map(|k| k.1 + 1).max().unwrap_or(0);
+        (w, h)
+    }
+    // '#' and '.' for probed points, blank for the ones never asked about.
+    fn render(&self) -> String {
+        let (w, h) = self.size();
+        let mut result = String::new();
+        for y in 0..h {
+            for x in 0..w {
+                result.push(match self.cache.get(&(x, y)) {
+                    Some(true) => '#',
+                    Some(false) => '.',
+                    None => ' ',
+                });
+            }
+            result.push('\n');
+        }
+        result
+    }
+    #[cfg(feature = "image")]
+    fn to_image(&self) -> image::RgbImage {
+        let (w, h) = self.size();
+        image::ImageBuffer::from_fn(w as u32, h as u32, |x, y| {
+            match self.cache.get(&(x as u64, y as u64)) {
+                Some(true) => image::Rgb([255u8, 255u8, 255u8]),
+                Some(false) => image::Rgb([200u8, 40u8, 40u8]),
+                None => image::Rgb([0u8, 0u8, 0u8]),
+            }
+        })
+    }
+    #[cfg(feature = "image")]
+    fn png(&self, name: &str) {
+        self.to_image().save(name).unwrap();
+    }
+    #[cfg(not(feature = "image"))]
+    fn png(&self, _: &str) {
+        panic!("built without the image feature");
+    }
+    fn save(&self, name: &str) {
+        if name.ends_with(".png") {
+            self.png(name);
+        } else {
+            fs::write(name, self.render()).unwrap();
+        }
     }
-    grid.iter().fold(0, |acc, r| acc + r.iter().sum::<u64>())
 }
 
-fn second() -> u64 {
-    let input = fs::read_to_string(env::args().nth(1).unwrap()).unwrap();
-    let memory = parse(&input);
-    let size = 1000;
-    let low = 950;
-    let target = 100;
-
-    for y in low..size {
-        let mut rightmost = 0;
-        for x in 0..size {
-            let scanner = Scanner::new((y * size + x) as u64, size as u64);
-            let mut intcode = IntCode::new(&memory, scanner);
-            intcode.run();
-            if let 1 = intcode.bus.output {
-                rightmost = x;
+fn first(oracle: &mut Oracle) -> u64 {
+    let mut result = 0;
+    for y in 0..50 {
+        for x in 0..50 {
+            if oracle.probe(x, y) {
+                result += 1;
             }
         }
-        let n_x = rightmost - target + 1;
-        let n_y = y + target - 1;
-        let scanner = Scanner::new((n_y * size + n_x) as u64, size as u64);
-        let mut intcode = IntCode::new(&memory, scanner);
-        intcode.run();
-        if let 1 = intcode.bus.output {
-            return 10000 * n_x + y;
+    }
+    result
+}
+
+// The beam is assumed to widen by less than this many cells per row, which
+// bounds the search on the rows near the emitter that miss it entirely.
+const MAX_SLOPE: u64 = 10;
+
+// Follows the left and right edges of the beam row by row, probing only
+// around where the edges were on the row above.
+struct Beam {
+    oracle: Oracle,
+    rows: Vec<Option<(u64, u64)>>,
+}
+
+impl Beam {
+    fn new(oracle: Oracle) -> Self {
+        Beam {
+            oracle,
+            rows: Vec::new(),
+        }
+    }
+    fn trace(&mut self, y: u64) -> Option<(u64, u64)> {
+        let (start, above) = self.rows.iter().rev().find_map(|r| *r).unwrap_or((0, 0));
+        let limit = start + y * MAX_SLOPE;
+        let left = (start..=limit).find(|x| self.oracle.probe(*x, y))?;
+        let mut right = above.max(left);
+        while self.oracle.probe(right + 1, y) {
+            right += 1;
+        }
+        Some((left, right))
+    }
+    fn edges(&mut self, y: u64) -> Option<(u64, u64)> {
+        while self.rows.len() as u64 <= y {
+            let row = self.trace(self.rows.len() as u64);
+            self.rows.push(row);
+        }
+        self.rows[y as usize]
+    }
+    fn left(&mut self, y: u64) -> Option<u64> {
+        Some(self.edges(y)?.0)
+    }
+    fn right(&mut self, y: u64) -> Option<u64> {
+        Some(self.edges(y)?.1)
+    }
+    // Top left corner of the first `width` x `height` square inside the beam,
+    // both at least 1. The beam only gets wider, so one always fits further
+    // down.
+    fn fit(&mut self, width: u64, height: u64) -> (u64, u64) {
+        let mut y = 0;
+        loop {
+            if let (Some(right), Some(left)) = (self.right(y), self.left(y + height - 1)) {
+                if right + 1 >= left + width {
+                    return (left, y);
+                }
+            }
+            y += 1;
         }
     }
-    0
+}
+
+fn second(beam: &mut Beam) -> u64 {
+    let width = env::args()
+        .nth(2)
+        .map_or(100, |n| n.parse::<u64>().unwrap());
+    let height = env::args()
+        .nth(3)
+        .map_or(width, |n| n.parse::<u64>().unwrap());
+    if width < 1 || height < 1 {
+        panic!("Invalid square size: {}x{}", width, height);
+    }
+    let (x, y) = beam.fit(width, height);
+    10000 * x + y
 }
 
 fn main() {
-    println!("first: {}", first());
-    println!("second: {}", second());
+    let input = fs::read_to_string(env::args().nth(1).unwrap()).unwrap();
+    let memory = parse(&input);
+    let mut oracle = Oracle::new(memory.clone());
+    println!("first: {} ({} probes)", first(&mut oracle), oracle.runs);
+    let mut beam = Beam::new(Oracle::new(memory));
+    let result = second(&mut beam);
+    println!("second: {} ({} probes)", result, beam.oracle.runs);
+    if let Some(name) = env::args().nth(4) {
+        beam.oracle.save(&name);
+    }
 }
 
 fn parse(input: &str) -> Vec<i64> {
@@ -1,15 +1,835 @@
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs;
-use std::io;
+use std::io::{self, BufRead};
 
 fn main() {
-    let input = fs::read_to_string(env::args().nth(1).unwrap()).unwrap();
-    let part = env::args().nth(2).unwrap_or("2".to_string()).parse::<usize>().unwrap();
-
+    let args = arguments();
+    let input = fs::read_to_string(&args[1]).unwrap();
+    let part = args.get(2).map_or(2, |p| p.parse::<usize>().unwrap());
+    let mode = if part == 1 { Mode::Walk } else { Mode::Run };
     let memory = parse(&input);
-    let mut intcode = IntCode::new(&memory, part);
+
+    let table = match args.get(3).map(|a| a.as_str()) {
+        Some("synth") => Ok(Table::new(mode)),
+        Some("formula") => Expr::parse(&args[4]).map(|e| Table::from_expr(mode, &e)),
+        Some("table") => Table::parse(mode, &fs::read_to_string(&args[4]).unwrap()),
+        _ => Err(String::new()),
+    };
+    match table {
+        Ok(table) => match search(&memory, mode, table) {
+            Ok((script, damage)) => {
+                print!("{}", script.compile());
+                println!("part {}: {}", part, damage);
+            }
+            Err(error) => println!("no springscript found: {}", error),
+        },
+        Err(error) if !error.is_empty() => println!("invalid jump condition: {}", error),
+        Err(_) => run(&memory, part, args.get(3..).unwrap_or(&[])),
+    }
+}
+
+// Value of a `--name value` option given anywhere on the command line.
+fn option(name: &str) -> Option<String> {
+    let args = env::args().collect::<Vec<_>>();
+    let i = args.iter().position(|a| a == name)?;
+    args.get(i + 1).cloned()
+}
+
+// Command line arguments without the `--name value` options.
+fn arguments() -> Vec<String> {
+    let mut result = Vec::new();
+    let mut args = env::args();
+    while let Some(arg) = args.next() {
+        if arg.starts_with("--") {
+            args.next();
+        } else {
+            result.push(arg);
+        }
+    }
+    result
+}
+
+// With `--script <file>` the springscript is read from the file instead of
+// stdin, and `--transcript <file>` saves everything the droid read and wrote.
+fn run(memory: &Vec<i64>, part: usize, hulls: &[String]) {
+    let source = match option("--script") {
+        Some(name) => read_script(part, io::BufReader::new(fs::File::open(name).unwrap())),
+        None => read_script(part, io::stdin().lock()),
+    };
+    let script = match Script::parse(&source) {
+        Ok(script) => script,
+        Err(error) => {
+            println!("invalid springscript: {}", error);
+            return;
+        }
+    };
+    // any further arguments are hulls to try the script on locally
+    if !hulls.is_empty() {
+        for hull in hulls.iter() {
+            match script.simulate(hull) {
+                Ok(()) => println!("{}: made it across", hull),
+                Err(p) => println!("{}: fell into the hole at {}", hull, p),
+            }
+        }
+        return;
+    }
+
+    let mut intcode = IntCode::new(memory, script.compile());
     intcode.run();
-    println!("part {}: {}", part, intcode.damage);
+    match outcome(&intcode) {
+        Ok(Outcome::Damage(damage)) => println!("part {}: {}", part, damage),
+        Ok(Outcome::Fell(failure)) => {
+            for line in failure.frames.last().unwrap() {
+                println!("{}", line);
+            }
+            println!(
+                "{}: fell into the hole at {} after {} frames",
+                failure.hull,
+                failure.position,
+                failure.frames.len()
+            );
+        }
+        Err(output) => println!("{}", output),
+    }
+    if let Some(name) = option("--transcript") {
+        fs::write(name, &intcode.transcript).unwrap();
+    }
+}
+
+// Reads springscript up to the WALK or RUN that starts the droid.
+fn read_script(part: usize, mut reader: impl BufRead) -> String {
+    let go = match part {
+        1 => "WALK",
+        2 => "RUN",
+        _ => panic!("unknown mode {}", part),
+    };
+    let mut result = String::new();
+    let mut buffer = String::new();
+    while buffer.trim() != go {
+        buffer = String::new();
+        match reader.read_line(&mut buffer) {
+            Err(error) => panic!("Error: {}", error),
+            Ok(0) => break,
+            Ok(_) => result.push_str(&buffer),
+        }
+    }
+    result
+}
+
+const MAX_INSTRUCTIONS: usize = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Register {
+    Sensor(usize),
+    T,
+    J,
+}
+
+impl Register {
+    fn parse(s: &str) -> Result<Register, String> {
+        match s {
+            "T" => Ok(Register::T),
+            "J" => Ok(Register::J),
+            _ if s.len() == 1 && ("A"..="I").contains(&s) => {
+                Ok(Register::Sensor((s.as_bytes()[0] - b'A') as usize))
+            }
+            _ => Err(format!("unknown register {}", s)),
+        }
+    }
+    fn name(&self) -> String {
+        match *self {
+            Register::Sensor(i) => ((b'A' + i as u8) as char).to_string(),
+            Register::T => "T".to_string(),
+            Register::J => "J".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    And,
+    Or,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Instruction {
+    op: Op,
+    x: Register,
+    y: Register,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Walk,
+    Run,
+}
+
+impl Mode {
+    fn sensors(&self) -> usize {
+        match *self {
+            Mode::Walk => 4,
+            Mode::Run => 9,
+        }
+    }
+    fn name(&self) -> &str {
+        match *self {
+            Mode::Walk => "WALK",
+            Mode::Run => "RUN",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Script {
+    instructions: Vec<Instruction>,
+    mode: Mode,
+}
+
+impl Script {
+    fn parse(text: &str) -> Result<Script, String> {
+        let mut instructions = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            let op = match words[..] {
+                [] => continue,
+                ["WALK"] => return Script::new(instructions, Mode::Walk),
+                ["RUN"] => return Script::new(instructions, Mode::Run),
+                ["AND", _, _] => Op::And,
+                ["OR", _, _] => Op::Or,
+                ["NOT", _, _] => Op::Not,
+                _ => return Err(format!("line {}: cannot parse '{}'", n + 1, line.trim())),
+            };
+            let register = |s| Register::parse(s).map_err(|e| format!("line {}: {}", n + 1, e));
+            instructions.push(Instruction {
+                op,
+                x: register(words[1])?,
+                y: register(words[2])?,
+            });
+        }
+        Err("missing WALK or RUN".to_string())
+    }
+
+    fn new(instructions: Vec<Instruction>, mode: Mode) -> Result<Script, String> {
+        if instructions.len() > MAX_INSTRUCTIONS {
+            return Err(format!(
+                "{} instructions, at most {} fit in memory",
+                instructions.len(),
+                MAX_INSTRUCTIONS
+            ));
+        }
+        for (n, i) in instructions.iter().enumerate() {
+            if let Register::Sensor(_) = i.y {
+                return Err(format!(
+                    "instruction {}: cannot write to read-only register {}",
+                    n + 1,
+                    i.y.name()
+                ));
+            }
+            if let Register::Sensor(s) = i.x {
+                if s >= mode.sensors() {
+                    return Err(format!(
+                        "instruction {}: register {} is not available in {} mode",
+                        n + 1,
+                        i.x.name(),
+                        mode.name()
+                    ));
+                }
+            }
+        }
+        Ok(Script { instructions, mode })
+    }
+
+    // Whether the droid jumps given what its sensors see, ground being true.
+    fn jumps(&self, sensors: &[bool]) -> bool {
+        let (mut t, mut j) = (false, false);
+        for i in self.instructions.iter() {
+            let x = match i.x {
+                Register::Sensor(s) => sensors[s],
+                Register::T => t,
+                Register::J => j,
+            };
+            let y = match i.y {
+                Register::T => &mut t,
+                _ => &mut j,
+            };
+            *y = match i.op {
+                Op::And => x && *y,
+                Op::Or => x || *y,
+                Op::Not => !x,
+            };
+        }
+        j
+    }
+
+    // Walks the droid over a hull like "#####.#..########", starting on its
+    // first cell. Returns where it fell, if it did.
+    fn simulate(&self, hull: &str) -> Result<(), usize> {
+        let ground = hull.chars().map(|c| c == '#').collect::<Vec<_>>();
+        let mut p = 0;
+        while p + 1 < ground.len() {
+            let sensors = (1..=self.mode.sensors())
+                .map(|k| *ground.get(p + k).unwrap_or(&true))
+                .collect::<Vec<_>>();
+            p += if self.jumps(&sensors) { 4 } else { 1 };
+            if !*ground.get(p).unwrap_or(&true) {
+                return Err(p);
+            }
+        }
+        Ok(())
+    }
+
+    fn compile(&self) -> String {
+        let mut result = String::new();
+        for i in self.instructions.iter() {
+            let op = match i.op {
+                Op::And => "AND",
+                Op::Or => "OR",
+                Op::Not => "NOT",
+            };
+            result.push_str(&format!("{} {} {}\n", op, i.x.name(), i.y.name()));
+        }
+        result.push_str(self.mode.name());
+        result.push('\n');
+        result
+    }
+}
+
+// A boolean formula over the sensors, e.g. "(!A | !B | !C) & D".
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Const(bool),
+    Sensor(usize),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn parse(text: &str) -> Result<Expr, String> {
+        let tokens = text
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<Vec<_>>();
+        let mut i = 0;
+        let expr = Expr::or(&tokens, &mut i)?;
+        if i < tokens.len() {
+            return Err(format!("unexpected '{}' at {}", tokens[i], i));
+        }
+        Ok(expr)
+    }
+    fn or(tokens: &[char], i: &mut usize) -> Result<Expr, String> {
+        let mut left = Expr::and(tokens, i)?;
+        while tokens.get(*i) == Some(&'|') {
+            *i += 1;
+            left = Expr::Or(Box::new(left), Box::new(Expr::and(tokens, i)?));
+        }
+        Ok(left)
+    }
+    fn and(tokens: &[char], i: &mut usize) -> Result<Expr, String> {
+        let mut left = Expr::factor(tokens, i)?;
+        while tokens.get(*i) == Some(&'&') {
+            *i += 1;
+            left = Expr::And(Box::new(left), Box::new(Expr::factor(tokens, i)?));
+        }
+        Ok(left)
+    }
+    fn factor(tokens: &[char], i: &mut usize) -> Result<Expr, String> {
+        let c = *tokens.get(*i).ok_or("unexpected end of formula")?;
+        *i += 1;
+        match c {
+            '!' => Ok(Expr::Not(Box::new(Expr::factor(tokens, i)?))),
+            '(' => {
+                let inner = Expr::or(tokens, i)?;
+                if tokens.get(*i) != Some(&')') {
+                    return Err(format!("missing ')' at {}", i));
+                }
+                *i += 1;
+                Ok(inner)
+            }
+            '0' => Ok(Expr::Const(false)),
+            '1' => Ok(Expr::Const(true)),
+            'A'..='I' => Ok(Expr::Sensor((c as u8 - b'A') as usize)),
+            _ => Err(format!("unexpected '{}' at {}", c, *i - 1)),
+        }
+    }
+    fn eval(&self, sensors: u32) -> bool {
+        match self {
+            Expr::Const(b) => *b,
+            Expr::Sensor(s) => sensors >> s & 1 == 1,
+            Expr::Not(e) => !e.eval(sensors),
+            Expr::And(a, b) => a.eval(sensors) && b.eval(sensors),
+            Expr::Or(a, b) => a.eval(sensors) || b.eval(sensors),
+        }
+    }
+}
+
+// Whether the droid has to jump for a given sensor reading, bit `i` being
+// sensor `i` seeing ground. Readings that are not in `rows` do not matter.
+#[derive(Debug, Clone)]
+struct Table {
+    sensors: usize,
+    rows: HashMap<u32, bool>,
+}
+
+impl Table {
+    fn new(mode: Mode) -> Self {
+        Table {
+            sensors: mode.sensors(),
+            rows: HashMap::new(),
+        }
+    }
+    fn from_expr(mode: Mode, expr: &Expr) -> Self {
+        let mut table = Table::new(mode);
+        for sensors in 0..1 << table.sensors {
+            table.rows.insert(sensors, expr.eval(sensors));
+        }
+        table
+    }
+    // Reads lines like "#.?# jump" or ".### stay", '?' matching either and
+    // missing sensors matching anything.
+    fn parse(mode: Mode, text: &str) -> Result<Self, String> {
+        let mut table = Table::new(mode);
+        for (n, line) in text.lines().enumerate() {
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            let (pattern, jump) = match words[..] {
+                [] => continue,
+                [pattern, "jump"] => (pattern, true),
+                [pattern, "stay"] => (pattern, false),
+                _ => return Err(format!("line {}: cannot parse '{}'", n + 1, line.trim())),
+            };
+            if pattern.len() > table.sensors || pattern.chars().any(|c| !"#.?".contains(c)) {
+                return Err(format!("line {}: bad pattern {}", n + 1, pattern));
+            }
+            for sensors in 0..1u32 << table.sensors {
+                let matches = pattern.chars().enumerate().all(|(i, c)| match c {
+                    '#' => sensors >> i & 1 == 1,
+                    '.' => sensors >> i & 1 == 0,
+                    _ => true,
+                });
+                if matches {
+                    table.rows.insert(sensors, jump);
+                }
+            }
+        }
+        Ok(table)
+    }
+    fn minterms(&self, jump: bool) -> Vec<u32> {
+        let mut result = self
+            .rows
+            .iter()
+            .filter(|(_, v)| **v == jump)
+            .map(|(k, _)| *k)
+            .collect::<Vec<_>>();
+        result.sort_unstable();
+        result
+    }
+    fn dont_cares(&self) -> Vec<u32> {
+        (0..1 << self.sensors)
+            .filter(|s| !self.rows.contains_key(s))
+            .collect()
+    }
+}
+
+// A product of sensor literals: bits in `mask` are left out, the others must
+// read as in `value`.
+type Implicant = (u32, u32);
+
+// Quine-McCluskey: the prime implicants of `on` with the help of `dc`, over
+// the first `sensors` bits.
+fn primes(on: &[u32], dc: &[u32], sensors: usize) -> Vec<Implicant> {
+    let unused = !((1 << sensors) - 1);
+    let mut current = on
+        .iter()
+        .chain(dc.iter())
+        .map(|m| (*m, unused))
+        .collect::<HashSet<Implicant>>();
+    let mut result = HashSet::new();
+    while !current.is_empty() {
+        let mut next = HashSet::new();
+        let mut combined = HashSet::new();
+        let list = current.iter().copied().collect::<Vec<_>>();
+        for (i, a) in list.iter().enumerate() {
+            for b in list[i + 1..].iter() {
+                let diff = a.0 ^ b.0;
+                if a.1 == b.1 && diff.count_ones() == 1 {
+                    next.insert((a.0 & !diff, a.1 | diff));
+                    combined.insert(*a);
+                    combined.insert(*b);
+                }
+            }
+        }
+        result.extend(current.difference(&combined).copied());
+        current = next;
+    }
+    result.into_iter().collect()
+}
+
+fn covers(i: &Implicant, m: u32) -> bool {
+    m & !i.1 == i.0
+}
+
+// Turns a set of products into instructions, see `compile_sop` and
+// `compile_pos`.
+type Compile = fn(&[Implicant]) -> Vec<Instruction>;
+
+// The set of primes covering every minterm of `on` that compiles shortest.
+fn cover(on: &[u32], primes: &[Implicant], compile: Compile) -> Vec<Implicant> {
+    let mut best = None;
+    cover_from(on, primes, compile, &mut Vec::new(), &mut best);
+    best.unwrap_or_default()
+}
+
+fn cover_from(
+    on: &[u32],
+    primes: &[Implicant],
+    compile: Compile,
+    chosen: &mut Vec<Implicant>,
+    best: &mut Option<Vec<Implicant>>,
+) {
+    let cost = |terms: &[Implicant]| compile(terms).len();
+    if let Some(b) = best {
+        if chosen.len() >= b.len() && cost(chosen) >= cost(b) {
+            return;
+        }
+    }
+    let missing = match on.iter().find(|m| !chosen.iter().any(|i| covers(i, **m))) {
+        Some(m) => *m,
+        None => {
+            *best = Some(chosen.clone());
+            return;
+        }
+    };
+    for p in primes.iter().filter(|p| covers(p, missing)) {
+        chosen.push(*p);
+        cover_from(on, primes, compile, chosen, best);
+        chosen.pop();
+    }
+}
+
+// Loads the product `i` into `r`, which still holds false if `clean`.
+fn compile_term(i: &Implicant, r: Register, clean: bool) -> Vec<Instruction> {
+    let ins = |op, x, y| Instruction { op, x, y };
+    let literals = (0..32).filter(|b| i.1 >> b & 1 == 0);
+    let (pos, neg): (Vec<usize>, Vec<usize>) = literals.partition(|b| i.0 >> b & 1 == 1);
+    let mut result = Vec::new();
+    match (neg.len(), pos.first()) {
+        (0, None) if clean => result.push(ins(Op::Not, r, r)),
+        (0, None) => {
+            result.push(ins(Op::Not, Register::Sensor(0), r));
+            result.push(ins(Op::Or, Register::Sensor(0), r));
+        }
+        (0, Some(p)) if clean => result.push(ins(Op::Or, Register::Sensor(*p), r)),
+        (0, Some(p)) => {
+            result.push(ins(Op::Not, Register::Sensor(*p), r));
+            result.push(ins(Op::Not, r, r));
+        }
+        (1, _) => result.push(ins(Op::Not, Register::Sensor(neg[0]), r)),
+        _ => {
+            // !a & !b & ... is !(a | b | ...)
+            if clean {
+                result.push(ins(Op::Or, Register::Sensor(neg[0]), r));
+            } else {
+                result.push(ins(Op::Not, Register::Sensor(neg[0]), r));
+                result.push(ins(Op::Not, r, r));
+            }
+            for n in neg[1..].iter() {
+                result.push(ins(Op::Or, Register::Sensor(*n), r));
+            }
+            result.push(ins(Op::Not, r, r));
+        }
+    }
+    let skip = if neg.is_empty() { 1 } else { 0 };
+    for p in pos.iter().skip(skip) {
+        result.push(ins(Op::And, Register::Sensor(*p), r));
+    }
+    result
+}
+
+// The sensor and its expected reading when `i` is a single literal.
+fn literal(i: &Implicant) -> Option<(usize, bool)> {
+    let mut literals = (0..32).filter(|b| i.1 >> b & 1 == 0);
+    match (literals.next(), literals.next()) {
+        (Some(b), None) => Some((b, i.0 >> b & 1 == 1)),
+        _ => None,
+    }
+}
+
+// Jumps on any of the products: the first one goes straight into J, a single
+// sensor is ORed in directly and every other product goes through T.
+fn compile_sop(terms: &[Implicant]) -> Vec<Instruction> {
+    let ins = |op, x, y| Instruction { op, x, y };
+    let mut result = Vec::new();
+    let mut clean = true;
+    for (n, term) in terms.iter().enumerate() {
+        if n == 0 {
+            result.extend(compile_term(term, Register::J, true));
+        } else if let Some((p, true)) = literal(term) {
+            result.push(ins(Op::Or, Register::Sensor(p), Register::J));
+        } else {
+            result.extend(compile_term(term, Register::T, clean));
+            result.push(ins(Op::Or, Register::T, Register::J));
+            clean = false;
+        }
+    }
+    peephole(result)
+}
+
+// Jumps on none of the products, as a product of sums: the first negated
+// product goes into J, a single sensor is ANDed in directly and every other
+// product is negated in T first.
+fn compile_pos(terms: &[Implicant]) -> Vec<Instruction> {
+    let ins = |op, x, y| Instruction { op, x, y };
+    if terms.is_empty() {
+        return vec![ins(Op::Not, Register::J, Register::J)];
+    }
+    let mut result = Vec::new();
+    let mut clean = true;
+    for (n, term) in terms.iter().enumerate() {
+        match literal(term) {
+            Some((p, false)) if n == 0 => {
+                result.push(ins(Op::Or, Register::Sensor(p), Register::J))
+            }
+            Some((p, false)) => result.push(ins(Op::And, Register::Sensor(p), Register::J)),
+            Some((p, true)) if n == 0 => {
+                result.push(ins(Op::Not, Register::Sensor(p), Register::J))
+            }
+            Some((p, true)) => {
+                result.push(ins(Op::Not, Register::Sensor(p), Register::T));
+                result.push(ins(Op::And, Register::T, Register::J));
+                clean = false;
+            }
+            None if n == 0 => {
+                result.extend(compile_term(term, Register::J, true));
+                result.push(ins(Op::Not, Register::J, Register::J));
+            }
+            None => {
+                result.extend(compile_term(term, Register::T, clean));
+                result.push(ins(Op::Not, Register::T, Register::T));
+                result.push(ins(Op::And, Register::T, Register::J));
+                clean = false;
+            }
+        }
+    }
+    peephole(result)
+}
+
+// Drops negations of a register that undo the one right before them.
+fn peephole(instructions: Vec<Instruction>) -> Vec<Instruction> {
+    let mut result: Vec<Instruction> = Vec::new();
+    for i in instructions {
+        let flip = i.op == Op::Not && i.x == i.y;
+        if flip && result.last() == Some(&i) {
+            result.pop();
+        } else {
+            result.push(i);
+        }
+    }
+    result
+}
+
+// Tries every order of the products, the most expensive one first otherwise.
+fn compile_best(terms: &[Implicant], compile: Compile) -> Vec<Instruction> {
+    if terms.len() > 6 {
+        let mut sorted = terms.to_vec();
+        sorted.sort_by_key(|t| std::cmp::Reverse(compile_term(t, Register::T, false).len()));
+        return compile(&sorted);
+    }
+    let mut best: Option<Vec<Instruction>> = None;
+    let mut order = terms.to_vec();
+    permutations(&mut order, 0, &mut |o| {
+        let candidate = compile(o);
+        let shorter = match &best {
+            Some(b) => candidate.len() < b.len(),
+            None => true,
+        };
+        if shorter {
+            best = Some(candidate);
+        }
+    });
+    best.unwrap_or_default()
+}
+
+fn permutations<F: FnMut(&[Implicant])>(v: &mut Vec<Implicant>, k: usize, f: &mut F) {
+    if k == v.len() {
+        f(v);
+        return;
+    }
+    for i in k..v.len() {
+        v.swap(k, i);
+        permutations(v, k + 1, f);
+        v.swap(k, i);
+    }
+}
+
+// The shortest of four scripts for the table: the minimized table as a sum of
+// products, the minimized complement as a product of sums, and the negations
+// of the complement as a sum and of the table as a product.
+fn synthesize(table: &Table, mode: Mode) -> Result<Script, String> {
+    let dc = table.dont_cares();
+    let on = table.minterms(true);
+    let off = table.minterms(false);
+    let on_primes = primes(&on, &dc, table.sensors);
+    let off_primes = primes(&off, &dc, table.sensors);
+    let negate = |mut instructions: Vec<Instruction>| {
+        instructions.push(Instruction {
+            op: Op::Not,
+            x: Register::J,
+            y: Register::J,
+        });
+        peephole(instructions)
+    };
+    let build = |minterms: &[u32], primes: &[Implicant], compile: Compile| {
+        compile_best(&cover(minterms, primes, compile), compile)
+    };
+    let candidates = vec![
+        build(&on, &on_primes, compile_sop),
+        build(&off, &off_primes, compile_pos),
+        negate(build(&off, &off_primes, compile_sop)),
+        negate(build(&on, &on_primes, compile_pos)),
+    ];
+    let mut best: Option<Vec<Instruction>> = None;
+    for candidate in candidates {
+        let shorter = match &best {
+            Some(b) => candidate.len() < b.len(),
+            None => true,
+        };
+        if shorter {
+            best = Some(candidate);
+        }
+    }
+    Script::new(best.unwrap_or_default(), mode)
+}
+
+// Looks for jumps that get the droid across every hull while agreeing with
+// what `table` already requires, and adds the readings that led to them.
+fn learn(table: &Table, hulls: &[String]) -> Option<Table> {
+    let grounds = hulls
+        .iter()
+        .map(|h| h.chars().map(|c| c == '#').collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let mut decided = Vec::new();
+    if !cross(table, &grounds, 0, &mut decided) {
+        return None;
+    }
+    let mut result = table.clone();
+    result.rows.extend(decided);
+    Some(result)
+}
+
+// Walks the droid from the start of `hulls[0]`, moving on to the next hull
+// once across, and backtracks over decisions when it falls.
+fn cross(table: &Table, hulls: &[Vec<bool>], p: usize, decided: &mut Vec<(u32, bool)>) -> bool {
+    let ground = match hulls.first() {
+        Some(ground) => ground,
+        None => return true,
+    };
+    if p + 1 >= ground.len() {
+        return cross(table, &hulls[1..], 0, decided);
+    }
+    let sensors = (1..=table.sensors)
+        .filter(|k| *ground.get(p + k).unwrap_or(&true))
+        .fold(0, |acc, k| acc | 1 << (k - 1));
+    let known = table
+        .rows
+        .get(&sensors)
+        .copied()
+        .or_else(|| decided.iter().find(|d| d.0 == sensors).map(|d| d.1));
+    let options = match known {
+        Some(jump) => vec![jump],
+        None => vec![false, true],
+    };
+    for jump in options {
+        let next = p + if jump { 4 } else { 1 };
+        if !*ground.get(next).unwrap_or(&true) {
+            continue;
+        }
+        let fresh = known.is_none();
+        if fresh {
+            decided.push((sensors, jump));
+        }
+        if cross(table, hulls, next, decided) {
+            return true;
+        }
+        if fresh {
+            decided.pop();
+        }
+    }
+    false
+}
+
+// What became of the droid on the real hull.
+enum Outcome {
+    Damage(u64),
+    Fell(Failure),
+}
+
+// The death animation shown when the droid does not make it across: the hull
+// it was walking, the hole it fell into and every frame of the way there.
+struct Failure {
+    hull: String,
+    position: usize,
+    frames: Vec<Vec<String>>,
+}
+
+impl Failure {
+    fn parse(output: &str) -> Option<Failure> {
+        let (_, animation) = output.split_once("Didn't make it across:")?;
+        let mut frames = Vec::new();
+        let mut frame = Vec::new();
+        for line in animation.lines().map(|l| l.trim()) {
+            if !line.is_empty() {
+                frame.push(line.to_string());
+            } else if !frame.is_empty() {
+                frames.push(frame);
+                frame = Vec::new();
+            }
+        }
+        if !frame.is_empty() {
+            frames.push(frame);
+        }
+        // the last frame has the droid down in the hole on the hull row
+        let last = frames.last()?;
+        let position = last.iter().find_map(|l| l.find('@'))?;
+        let hull = last.last()?.replace('@', ".");
+        Some(Failure {
+            hull,
+            position,
+            frames,
+        })
+    }
+}
+
+fn outcome(intcode: &IntCode) -> Result<Outcome, String> {
+    if intcode.damage > 0 {
+        return Ok(Outcome::Damage(intcode.damage));
+    }
+    match Failure::parse(&intcode.output) {
+        Some(failure) => Ok(Outcome::Fell(failure)),
+        None => Err(intcode.output.trim().to_string()),
+    }
+}
+
+// Keeps synthesizing scripts and running them, learning from every hull the
+// droid falls into until one makes it across.
+fn search(memory: &Vec<i64>, mode: Mode, table: Table) -> Result<(Script, u64), String> {
+    let mut hulls = Vec::new();
+    let mut learned = table.clone();
+    loop {
+        let script = synthesize(&learned, mode)?;
+        let mut intcode = IntCode::new(memory, script.compile());
+        intcode.run();
+        let hull = match outcome(&intcode)? {
+            Outcome::Damage(damage) => return Ok((script, damage)),
+            Outcome::Fell(failure) => failure.hull,
+        };
+        if hulls.contains(&hull) {
+            return Err(format!("script keeps falling on {}", hull));
+        }
+        hulls.push(hull);
+        learned = learn(&table, &hulls).ok_or("no jumps get across every hull seen")?;
+    }
 }
 
 fn parse(input: &str) -> Vec<i64> {
@@ -51,23 +871,21 @@ struct IntCode {
     base: i64,
     input_buf: String,
     input_idx: usize,
-    go: String,
+    output: String,
+    transcript: String,
     damage: u64,
 }
 
 impl IntCode {
-    fn new(memory: &Vec<i64>, mode: usize) -> Self {
+    fn new(memory: &Vec<i64>, input_buf: String) -> Self {
         IntCode {
             memory: memory.to_vec(),
             ptr: 0,
             base: 0,
-            input_buf: String::new(),
+            input_buf,
             input_idx: 0,
-            go: match mode {
-                1 => String::from("WALK\n"),
-                2 => String::from("RUN\n"),
-                _ => panic!("unknown mode {}", mode),
-            },
+            output: String::new(),
+            transcript: String::new(),
             damage: 0,
         }
     }
@@ -131,20 +949,10 @@ impl IntCode {
                 i + 4
             }
             3 => {
-                // if input_buf is empty, get from stdin
-                if self.input_buf.is_empty() {
-                    let mut buffer = String::new();
-                    while buffer != self.go {
-                        buffer = String::new();
-                        match io::stdin().read_line(&mut buffer) {
-                            Err(error) => panic!("Error: {}", error),
-                            Ok(_) => self.input_buf.push_str(&buffer),
-                        }
-                    }
-                }
                 let input_val = ((&self.input_buf[self.input_idx..self.input_idx+1]).parse::<char>().unwrap()
                     as u8) as i64;
                 self.input_idx += 1;
+                self.transcript.push(input_val as u8 as char);
                 self.set(i + 1, arg1_mode, input_val);
                 i + 2
             }
@@ -152,8 +960,10 @@ impl IntCode {
                 let output_val = self.get(i + 1, arg1_mode);
                 if output_val > u8::max_value() as i64 {
                     self.damage = output_val as u64;
+                    self.transcript.push_str(&format!("{}\n", output_val));
                 } else {
-                    print!("{}", (output_val as u8) as char);
+                    self.output.push((output_val as u8) as char);
+                    self.transcript.push((output_val as u8) as char);
                 }
                 i + 2
             }
@@ -209,3 +1019,172 @@ impl IntCode {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(text: &str) -> String {
+        Script::parse(text).unwrap_err()
+    }
+
+    #[test]
+    fn parse_script() {
+        let script = Script::parse("NOT A J\n\nOR D T\nAND T J\nRUN\n").unwrap();
+        assert_eq!(script.mode, Mode::Run);
+        assert_eq!(script.instructions.len(), 3);
+        assert_eq!(script.compile(), "NOT A J\nOR D T\nAND T J\nRUN\n");
+    }
+
+    #[test]
+    fn too_many_instructions() {
+        let text = "NOT A J\n".repeat(16) + "WALK\n";
+        assert_eq!(error(&text), "16 instructions, at most 15 fit in memory");
+        assert!(Script::parse(&("NOT A J\n".repeat(15) + "WALK\n")).is_ok());
+    }
+
+    #[test]
+    fn read_only_register() {
+        assert_eq!(
+            error("NOT A J\nNOT J D\nWALK\n"),
+            "instruction 2: cannot write to read-only register D"
+        );
+    }
+
+    #[test]
+    fn sensor_out_of_range() {
+        assert_eq!(
+            error("NOT E J\nWALK\n"),
+            "instruction 1: register E is not available in WALK mode"
+        );
+        assert!(Script::parse("NOT E J\nRUN\n").is_ok());
+        assert_eq!(error("NOT K J\nRUN\n"), "line 1: unknown register K");
+    }
+
+    #[test]
+    fn malformed_script() {
+        assert_eq!(error("NOT A\nWALK\n"), "line 1: cannot parse 'NOT A'");
+        assert_eq!(error("NOT A J\n"), "missing WALK or RUN");
+    }
+
+    // jumping over any hole right ahead
+    #[test]
+    fn simulate_hulls() {
+        let script = Script::parse("NOT A J\nWALK\n").unwrap();
+        assert_eq!(script.simulate("#####.#####"), Ok(()));
+        assert_eq!(script.simulate("#####.#..####"), Err(8));
+    }
+
+    // The end of a run that fell into the first hole, as the droid prints it.
+    const DEATH: &str = "Input instructions:
+
+Walking...
+
+
+Didn't make it across:
+
+.................
+.................
+@................
+#####.###########
+
+.................
+.................
+.....@...........
+#####.###########
+
+.................
+.................
+.................
+#####@###########
+
+";
+
+    #[test]
+    fn parse_failure() {
+        let failure = Failure::parse(DEATH).unwrap();
+        assert_eq!(failure.hull, "#####.###########");
+        assert_eq!(failure.position, 5);
+        assert_eq!(failure.frames.len(), 3);
+        assert_eq!(failure.frames[1][2], ".....@...........");
+        assert!(Failure::parse("Walking...\n").is_none());
+    }
+
+    // The conditions that get the droid across in both parts of the puzzle,
+    // and a few edge cases.
+    const FORMULAS: [(Mode, &str); 6] = [
+        (Mode::Walk, "(!A | !B | !C) & D"),
+        (Mode::Run, "(!A | !B | !C) & D & (E | H)"),
+        (Mode::Walk, "A | B | C | D"),
+        (Mode::Run, "!A & !B & E & !F"),
+        (Mode::Walk, "A & !A"),
+        (Mode::Walk, "A | !A"),
+    ];
+
+    #[test]
+    fn synthesized_scripts_match_their_tables() {
+        for (mode, formula) in FORMULAS.iter() {
+            let expr = Expr::parse(formula).unwrap();
+            let script = synthesize(&Table::from_expr(*mode, &expr), *mode).unwrap();
+            for sensors in 0..1u32 << mode.sensors() {
+                let ground = (0..mode.sensors())
+                    .map(|i| sensors >> i & 1 == 1)
+                    .collect::<Vec<_>>();
+                let jumps = script.jumps(&ground);
+                assert_eq!(jumps, expr.eval(sensors), "{} at {:b}", formula, sensors);
+            }
+        }
+    }
+
+    #[test]
+    fn synthesized_scripts_are_short() {
+        let length = |mode, formula| {
+            let table = Table::from_expr(mode, &Expr::parse(formula).unwrap());
+            synthesize(&table, mode).unwrap().instructions.len()
+        };
+        assert_eq!(length(Mode::Walk, "A | B | C | D"), 4);
+        assert_eq!(length(Mode::Walk, "(!A | !B | !C) & D"), 5);
+        assert_eq!(length(Mode::Walk, "A & !A"), 0);
+    }
+
+    #[test]
+    fn synthesized_scripts_cross_hulls() {
+        let walk = [
+            "#####.###########",
+            "#####...#########",
+            "#####..#.########",
+        ];
+        let run = [
+            "#####.#.##..#####",
+            "#####.##.##..####",
+            "#####.#.##.#.####",
+        ];
+        for ((mode, formula), hulls) in FORMULAS.iter().zip([walk, run].iter()) {
+            let table = Table::from_expr(*mode, &Expr::parse(formula).unwrap());
+            let script = synthesize(&table, *mode).unwrap();
+            for hull in hulls.iter() {
+                assert_eq!(script.simulate(hull), Ok(()), "{} on {}", formula, hull);
+            }
+        }
+    }
+
+    // the parity of four sensors needs every minterm in either form
+    #[test]
+    fn synthesized_script_too_long() {
+        let mut text = String::new();
+        for sensors in 0..16u32 {
+            let pattern = (0..4)
+                .map(|i| if sensors >> i & 1 == 1 { '#' } else { '.' })
+                .collect::<String>();
+            let verdict = if sensors.count_ones() % 2 == 1 {
+                "jump"
+            } else {
+                "stay"
+            };
+            text.push_str(&format!("{} {}\n", pattern, verdict));
+        }
+        let table = Table::parse(Mode::Walk, &text).unwrap();
+        let error = synthesize(&table, Mode::Walk).unwrap_err();
+        assert!(error.ends_with("at most 15 fit in memory"), "{}", error);
+    }
+}
@@ -1,6 +1,8 @@
-use itertools::Itertools;
+#[cfg(feature = "json")]
+use serde_json::{json, Value};
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::env;
+use std::fmt;
 use std::fs;
 use std::io;
 
@@ -38,6 +40,20 @@ impl Direction {
             Direction::West => String::from("west"),
         }
     }
+    fn offset(&self) -> (i64, i64) {
+        match self {
+            Direction::North => (0, -1),
+            Direction::South => (0, 1),
+            Direction::East => (1, 0),
+            Direction::West => (-1, 0),
+        }
+    }
+    fn connector(&self) -> char {
+        match self {
+            Direction::North | Direction::South => '|',
+            Direction::East | Direction::West => '-',
+        }
+    }
     fn to_command(&self) -> String {
         match self {
             Direction::North => String::from("north\n"),
@@ -86,246 +102,826 @@ fn get_path(
     result.into_iter().rev().collect()
 }
 
-fn main() {
-    let input = fs::read_to_string(env::args().nth(1).unwrap()).unwrap();
+// What the game said in answer to a command.
+#[derive(Debug, Clone, PartialEq)]
+enum Event {
+    Room {
+        name: String,
+        description: String,
+        doors: Vec<Direction>,
+        items: Vec<String>,
+    },
+    Took(String),
+    Dropped(String),
+    Inventory(Vec<String>),
+    Ejected {
+        too_heavy: bool,
+    },
+    Password(u64),
+    GameOver(String),
+    Message(String),
+}
 
-    let memory = parse(&input);
-    let mut intcode = IntCode::new(&memory);
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::Room {
+                name,
+                description,
+                doors,
+                items,
+            } => {
+                let doors = doors.iter().map(|d| d.to_string()).collect::<Vec<_>>();
+                write!(f, "room {}: {} [{}]", name, description, doors.join(", "))?;
+                if !items.is_empty() {
+                    write!(f, " items: {}", items.join(", "))?;
+                }
+                Ok(())
+            }
+            Event::Took(item) => write!(f, "took {}", item),
+            Event::Dropped(item) => write!(f, "dropped {}", item),
+            Event::Inventory(items) if items.is_empty() => write!(f, "carrying nothing"),
+            Event::Inventory(items) => write!(f, "carrying: {}", items.join(", ")),
+            Event::Ejected { too_heavy: true } => write!(f, "ejected: too heavy"),
+            Event::Ejected { too_heavy: false } => write!(f, "ejected: too light"),
+            Event::Password(password) => write!(f, "password: {}", password),
+            Event::GameOver(reason) => write!(f, "game over: {}", reason),
+            Event::Message(text) => write!(f, "{}", text),
+        }
+    }
+}
 
-    let mut next_moves = vec![];
-    let mut next_items = vec![];
-    let bad = [
-        "molten lava",
-        "giant electromagnet",
-        "infinite loop",
-        "escape pod",
-        "photons",
-    ];
-
-    let mut navigation = HashMap::new();
-    let mut my_items = vec![];
-    let mut current_location = String::new();
-    let mut previous_location = String::new();
-    let mut next_command = String::new();
-    let mut pending_commands = vec![];
+// Reads the `- entry` lines of a list following its header at `lines[*i]`.
+fn list(lines: &[&str], i: &mut usize) -> Vec<String> {
+    let mut result = Vec::new();
+    *i += 1;
+    while let Some(entry) = lines.get(*i).and_then(|l| l.strip_prefix("- ")) {
+        result.push(entry.to_string());
+        *i += 1;
+    }
+    result
+}
 
-    loop {
-        if !pending_commands.is_empty() {
-            println!("pending commands: {:?}", pending_commands);
-        }
-        if let Some(c) = pending_commands.pop() {
-            next_command = c;
-        }
-        let direction = Direction::parse(&next_command);
-        if intcode.input_buf.is_empty() {
-            std::mem::swap(&mut intcode.input_buf, &mut next_command);
-            intcode.output_buf.clear();
-        }
-        intcode.run();
-        // print to screen!
-        print!("{}", intcode.output_buf);
-        let lines = intcode
-            .output_buf
-            .lines()
-            .map(|s| s.trim().to_string())
-            .collect::<Vec<String>>();
-        let mut locations = vec![];
-        for (i, line) in lines.iter().enumerate() {
-            if line.contains("==") {
-                current_location = (&line[3..line.len() - 3]).to_string();
-                navigation
-                    .entry(current_location.to_owned())
-                    .or_insert(HashMap::new());
-                locations.push(current_location.to_owned());
-            }
-            if line == "Doors here lead:" {
-                next_moves.clear();
-                let mut j = 1;
-                while !lines[i + j].is_empty() {
-                    let direction = (&lines[i + j][2..]).to_string();
-                    if let Some(n) = navigation.get_mut(&current_location) {
-                        (*n).entry(Direction::parse(&direction).unwrap())
-                            .or_insert(String::new());
-                    }
-                    next_moves.push(direction);
-                    j += 1;
-                }
+fn parse_response(output: &str) -> Vec<Event> {
+    let lines = output.lines().map(|l| l.trim()).collect::<Vec<_>>();
+    let mut events = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if line.is_empty() || line == "Command?" {
+            i += 1;
+        } else if line.starts_with("== ") && line.ends_with(" ==") {
+            let name = line[3..line.len() - 3].to_string();
+            let mut description = Vec::new();
+            i += 1;
+            while i < lines.len() && !lines[i].is_empty() {
+                description.push(lines[i]);
+                i += 1;
             }
-            if line == "Items here:" {
-                next_items.clear();
-                let mut j = 1;
-                while !lines[i + j].is_empty() {
-                    let item = &lines[i + j][2..];
-                    if !bad.contains(&item) {
-                        next_items.push(item.to_string());
+            let mut doors = Vec::new();
+            let mut items = Vec::new();
+            loop {
+                while i < lines.len() && lines[i].is_empty() {
+                    i += 1;
+                }
+                match lines.get(i) {
+                    Some(&"Doors here lead:") => {
+                        doors = list(&lines, &mut i)
+                            .iter()
+                            .filter_map(Direction::parse)
+                            .collect()
                     }
-                    j += 1;
+                    Some(&"Items here:") => items = list(&lines, &mut i),
+                    _ => break,
                 }
             }
+            events.push(Event::Room {
+                name,
+                description: description.join(" "),
+                doors,
+                items,
+            });
+        } else if line == "Items in your inventory:" {
+            events.push(Event::Inventory(list(&lines, &mut i)));
+        } else {
+            i += 1;
+            let event = if let Some(item) = line.strip_prefix("You take the ") {
+                Event::Took(item.trim_end_matches('.').to_string())
+            } else if let Some(item) = line.strip_prefix("You drop the ") {
+                Event::Dropped(item.trim_end_matches('.').to_string())
+            } else if line == "You aren't carrying any items." {
+                Event::Inventory(Vec::new())
+            } else if line.contains("Alert! Droids on this ship are") {
+                // lighter than the detected value means we weigh too much
+                Event::Ejected {
+                    too_heavy: line.contains("lighter"),
+                }
+            } else if line.contains("on the keypad") {
+                match line.split_whitespace().find_map(|w| w.parse::<u64>().ok()) {
+                    Some(password) => Event::Password(password),
+                    None => Event::Message(line.to_string()),
+                }
+            } else {
+                Event::Message(line.to_string())
+            };
+            events.push(event);
         }
-        if let Some(dir) = direction {
-            match locations.len() {
-                0 => unreachable!(),
-                1 => {
-                    if let Some(n) = navigation.get_mut(&previous_location) {
-                        (*n).insert(dir, current_location.to_owned());
-                    }
-                    if let Some(n) = navigation.get_mut(&current_location) {
-                        (*n).insert(Direction::opposite(dir), previous_location.to_owned());
+    }
+    // the game only stops asking for commands once it has ended
+    let won = events.iter().any(|e| matches!(e, Event::Password(_)));
+    if !won && !output.is_empty() && !output.trim_end().ends_with("Command?") {
+        let reason = match events.last() {
+            Some(Event::Message(text)) => text.to_string(),
+            _ => String::from("the program halted"),
+        };
+        if let Some(Event::Message(_)) = events.last() {
+            events.pop();
+        }
+        events.push(Event::GameOver(reason));
+    }
+    events
+}
+
+// How many instructions a command may take before we decide the game is
+// never going to ask for the next one.
+const STEP_BUDGET: usize = 1_000_000;
+
+// Ways an item can ruin the run once picked up.
+#[derive(Debug, Clone, PartialEq)]
+enum Hazard {
+    GameOver(String),
+    Loops,
+    Stuck,
+}
+
+impl fmt::Display for Hazard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Hazard::GameOver(reason) => write!(f, "{}", reason),
+            Hazard::Loops => write!(f, "the game never asks for another command"),
+            Hazard::Stuck => write!(f, "the droid can no longer move"),
+        }
+    }
+}
+
+// Sends a command to a copy of the machine, returning what the game answered
+// or None if it ran out of budget.
+fn try_command(intcode: &mut IntCode, command: &str) -> Option<Vec<Event>> {
+    intcode.input_buf = format!("{}\n", command);
+    intcode.output_buf.clear();
+    if !intcode.run_for(STEP_BUDGET) {
+        return None;
+    }
+    Some(parse_response(&intcode.output_buf))
+}
+
+// Picks the item up on a snapshot of the machine and checks that the game
+// goes on and that the droid can still walk through `door` afterwards.
+fn hazard(intcode: &IntCode, item: &str, door: Option<&Direction>) -> Option<Hazard> {
+    let mut trial = intcode.clone();
+    let events = match try_command(&mut trial, &format!("take {}", item)) {
+        Some(events) => events,
+        None => return Some(Hazard::Loops),
+    };
+    if let Some(Event::GameOver(reason)) = events.last() {
+        return Some(Hazard::GameOver(reason.to_string()));
+    }
+    let door = door?;
+    match try_command(&mut trial, &door.to_string()) {
+        None => Some(Hazard::Loops),
+        Some(events) => match events.last() {
+            Some(Event::GameOver(reason)) => Some(Hazard::GameOver(reason.to_string())),
+            _ if !events.iter().any(|e| matches!(e, Event::Room { .. })) => Some(Hazard::Stuck),
+            _ => None,
+        },
+    }
+}
+
+fn main() {
+    let input = fs::read_to_string(env::args().nth(1).unwrap()).unwrap();
+
+    let memory = parse(&input);
+    let mut intcode = IntCode::new(&memory);
+    let ship = match option("--script") {
+        Some(name) => walkthrough(&mut intcode, &fs::read_to_string(name).unwrap()),
+        None if env::args().any(|a| a == "--play") => shell(&mut intcode),
+        None => explore(&mut intcode),
+    };
+    if let Some(name) = option("--transcript") {
+        fs::write(name, &intcode.transcript).unwrap();
+    }
+    if let Some(name) = option("--export") {
+        ship.save(&name);
+    }
+}
+
+// Value of a `--name value` option given anywhere on the command line.
+fn option(name: &str) -> Option<String> {
+    let args = env::args().collect::<Vec<_>>();
+    let i = args.iter().position(|a| a == name)?;
+    args.get(i + 1).cloned()
+}
+
+// Prints what the game said, or with `--events` the events parsed out of it.
+fn show(output: &str) {
+    if env::args().any(|a| a == "--events") {
+        for event in parse_response(output) {
+            println!("{}", event);
+        }
+    } else {
+        print!("{}", output);
+    }
+}
+
+// Plays the commands of a walkthrough file, one per line, skipping blank lines
+// and `#` comments. Stops early if the droid no longer asks for a command.
+fn walkthrough(intcode: &mut IntCode, script: &str) -> Ship {
+    let mut ship = Ship::default();
+    let mut over = ended(&send(intcode, &mut ship, ""));
+    let commands = script
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'));
+    for command in commands {
+        if over {
+            break;
+        }
+        over = ended(&send(intcode, &mut ship, command));
+    }
+    ship
+}
+
+// Everything learned about the ship so far: where each door leads (an empty
+// name for doors nobody went through yet), what lies in each room and which
+// items turned out to be hazards.
+#[derive(Clone, Default)]
+struct Ship {
+    navigation: HashMap<String, HashMap<Direction, String>>,
+    items: HashMap<String, Vec<String>>,
+    hazards: HashMap<String, Option<Hazard>>,
+    current: String,
+}
+
+impl Ship {
+    // Records the rooms the game showed in answer to `command`. When it was a
+    // move, the first room shown is where the door led; the droid can still
+    // end up in another one, as when the checkpoint ejects it.
+    fn update(&mut self, command: &str, events: &[Event]) {
+        let previous = self.current.to_owned();
+        let mut rooms = Vec::new();
+        for event in events {
+            match event {
+                Event::Room {
+                    name, doors, items, ..
+                } => {
+                    let exits = self.navigation.entry(name.to_owned()).or_default();
+                    for door in doors.iter() {
+                        exits.entry(*door).or_default();
                     }
+                    self.items.insert(name.to_owned(), items.clone());
+                    rooms.push(name.to_owned());
                 }
-                _ => {
-                    if let Some(n) = navigation.get_mut(&previous_location) {
-                        (*n).insert(dir, locations[0].to_string());
+                Event::Took(item) => {
+                    if let Some(items) = self.items.get_mut(&self.current) {
+                        items.retain(|i| i != item);
                     }
-                    if let Some(n) = navigation.get_mut(&locations[0]) {
-                        (*n).insert(Direction::opposite(dir), previous_location.to_owned());
+                }
+                Event::Dropped(item) => {
+                    if let Some(items) = self.items.get_mut(&self.current) {
+                        items.push(item.to_owned());
                     }
                 }
+                _ => (),
             }
         }
-        if !next_items.is_empty() {
-            next_command = String::from("take ");
-            let item = &next_items.pop().unwrap();
-            my_items.push(item.to_string());
-            next_command.push_str(item);
-            next_command.push('\n');
+        if let (Some(first), Some(dir)) = (rooms.first(), Direction::parse(&command.to_string())) {
+            if let Some(n) = self.navigation.get_mut(&previous) {
+                n.insert(dir, first.to_owned());
+            }
+            if let Some(n) = self.navigation.get_mut(first) {
+                n.insert(Direction::opposite(dir), previous);
+            }
+        }
+        if let Some(last) = rooms.pop() {
+            self.current = last;
+        }
+    }
+
+    // Commands leading from the current room to `room`.
+    fn path(&self, room: &str) -> Vec<String> {
+        get_path(&self.navigation, &self.current, &room.to_string())
+            .into_iter()
+            .rev()
+            .map(|c| c.trim().to_string())
+            .collect()
+    }
+
+    // Commands leading to the nearest room with items that may be worth
+    // taking, or through the nearest door nobody went through yet.
+    fn unexplored(&self) -> Option<Vec<String>> {
+        let rooms = self
+            .items
+            .iter()
+            .filter(|(room, _)| **room != self.current)
+            .filter(|(_, items)| {
+                items
+                    .iter()
+                    .any(|i| !matches!(self.hazards.get(i), Some(Some(_))))
+            })
+            .map(|(room, _)| self.path(room));
+        let doors = self
+            .navigation
+            .iter()
+            .flat_map(|(room, exits)| {
+                exits
+                    .iter()
+                    .filter(|(_, n)| n.is_empty())
+                    .map(move |(d, _)| (room, d))
+            })
+            .map(|(room, d)| {
+                let mut path = self.path(room);
+                path.push(d.to_string());
+                path
+            });
+        rooms.chain(doors).min_by_key(|path| path.len())
+    }
+
+    fn hazard(&mut self, intcode: &IntCode, item: &str) -> Option<Hazard> {
+        let door = self
+            .navigation
+            .get(&self.current)
+            .and_then(|exits| exits.keys().next().copied());
+        self.hazards
+            .entry(item.to_string())
+            .or_insert_with(|| hazard(intcode, item, door.as_ref()))
+            .clone()
+    }
+
+    fn is_hazard(&self, item: &str) -> bool {
+        matches!(self.hazards.get(item), Some(Some(_)))
+    }
+
+    // Places the rooms on a grid by following the doors from the droid's
+    // room. Rooms that would land on a cell already taken are left out.
+    fn layout(&self) -> Vec<(String, (i64, i64))> {
+        let mut placed: Vec<(String, (i64, i64))> = Vec::new();
+        let mut taken = HashMap::new();
+        let mut clashes: Vec<String> = Vec::new();
+        let mut queue = VecDeque::new();
+        queue.push_back((self.current.to_owned(), (0, 0)));
+        while let Some((room, (x, y))) = queue.pop_front() {
+            if placed.iter().any(|(r, _)| *r == room) || clashes.contains(&room) {
+                continue;
+            }
+            if taken.contains_key(&(x, y)) {
+                clashes.push(room);
+                continue;
+            }
+            taken.insert((x, y), room.to_owned());
+            placed.push((room.to_owned(), (x, y)));
+            let mut exits = self.navigation[&room].iter().collect::<Vec<_>>();
+            exits.sort_by_key(|(d, _)| d.to_string());
+            for (d, n) in exits.into_iter().filter(|(_, n)| !n.is_empty()) {
+                let (dx, dy) = d.offset();
+                queue.push_back((n.to_owned(), (x + dx, y + dy)));
+            }
+        }
+        placed
+    }
+
+    // The grid layout as ASCII: every room is a numbered box, `*` marks the
+    // droid, `!` a room holding a hazard and `?` a door nobody went through.
+    fn to_grid(&self) -> String {
+        const WIDTH: i64 = 6;
+        let placed = self.layout();
+        if placed.is_empty() {
+            return String::new();
         }
-        if next_command.is_empty() {
-            if let Some(d) = navigation[&current_location]
+        let mut missing = self
+            .navigation
+            .keys()
+            .filter(|room| !placed.iter().any(|(r, _)| r == *room))
+            .cloned()
+            .collect::<Vec<_>>();
+        missing.sort();
+        let min_x = placed.iter().map(|(_, p)| p.0).min().unwrap();
+        let max_x = placed.iter().map(|(_, p)| p.0).max().unwrap();
+        let min_y = placed.iter().map(|(_, p)| p.1).min().unwrap();
+        let max_y = placed.iter().map(|(_, p)| p.1).max().unwrap();
+        let columns = ((max_x - min_x + 1) * WIDTH + 1) as usize;
+        let rows = ((max_y - min_y + 1) * 2 + 1) as usize;
+        let mut canvas = vec![vec![' '; columns]; rows];
+        let mut legend = String::new();
+        for (i, (room, (x, y))) in placed.iter().enumerate() {
+            let col = ((x - min_x) * WIDTH + 1) as usize;
+            let row = ((y - min_y) * 2 + 1) as usize;
+            let items = self.items.get(room).cloned().unwrap_or_default();
+            let mark = if *room == self.current {
+                '*'
+            } else if items.iter().any(|item| self.is_hazard(item)) {
+                '!'
+            } else {
+                ' '
+            };
+            for (k, c) in format!("[{:>2}{}]", i, mark).chars().enumerate() {
+                canvas[row][col + k] = c;
+            }
+            for (d, n) in self.navigation[room].iter() {
+                let c = if n.is_empty() { '?' } else { d.connector() };
+                match d {
+                    Direction::North => canvas[row - 1][col + 2] = c,
+                    Direction::South => canvas[row + 1][col + 2] = c,
+                    Direction::East => canvas[row][col + 5] = c,
+                    Direction::West => canvas[row][col - 1] = c,
+                }
+            }
+            let items = items
                 .iter()
-                .filter_map(|(k, v)| if v.is_empty() { Some(k) } else { None })
-                .next()
-            {
-                next_command = d.to_string();
-                next_command.push('\n');
-            }
-        }
-        previous_location = current_location.to_owned();
-
-        println!("\n\nNext Command: {}", next_command);
-        if next_command.is_empty() {
-            let mut next = None;
-            for (c, m) in navigation.iter() {
-                for (_, n) in m.iter() {
-                    if n.is_empty() {
-                        next = Some(c);
+                .map(|item| {
+                    if self.is_hazard(item) {
+                        format!("{} (hazard)", item)
+                    } else {
+                        item.to_string()
                     }
+                })
+                .collect::<Vec<_>>();
+            legend.push_str(&format!("{:>3}  {}", i, room));
+            if !items.is_empty() {
+                legend.push_str(&format!(": {}", items.join(", ")));
+            }
+            legend.push('\n');
+        }
+        let mut result = canvas
+            .iter()
+            .map(|row| row.iter().collect::<String>().trim_end().to_string() + "\n")
+            .collect::<String>();
+        result.push_str(&legend);
+        if !missing.is_empty() {
+            result.push_str(&format!("not placed: {}\n", missing.join(", ")));
+        }
+        result
+    }
+
+    fn to_dot(&self) -> String {
+        let mut rooms = self.navigation.keys().collect::<Vec<_>>();
+        rooms.sort();
+        let mut result = String::from("digraph ship {\n");
+        for room in rooms.iter() {
+            let items = self.items.get(*room).cloned().unwrap_or_default();
+            let mut label = room.to_string();
+            for item in items.iter() {
+                label.push_str("\\n");
+                label.push_str(item);
+                if self.is_hazard(item) {
+                    label.push_str(" (hazard)");
                 }
             }
-            match next {
-                Some(d) => pending_commands = get_path(&navigation, &current_location, &d),
-                None => {
-                    pending_commands = get_path(
-                        &navigation,
-                        &current_location,
-                        &"Security Checkpoint".to_string(),
-                    )
+            let mut attributes = format!("label=\"{}\"", label);
+            if items.iter().any(|item| self.is_hazard(item)) {
+                attributes.push_str(", color=red");
+            }
+            if **room == self.current {
+                attributes.push_str(", style=bold");
+            }
+            result.push_str(&format!("  \"{}\" [{}];\n", room, attributes));
+        }
+        for room in rooms.iter() {
+            let mut exits = self.navigation[*room].iter().collect::<Vec<_>>();
+            exits.sort_by_key(|(d, _)| d.to_string());
+            for (d, n) in exits {
+                if n.is_empty() {
+                    let door = format!("{} {}", room, d.to_string());
+                    result.push_str(&format!("  \"{}\" [shape=point];\n", door));
+                    result.push_str(&format!(
+                        "  \"{}\" -> \"{}\" [label=\"{}\", style=dashed];\n",
+                        room,
+                        door,
+                        d.to_string()
+                    ));
+                } else {
+                    result.push_str(&format!(
+                        "  \"{}\" -> \"{}\" [label=\"{}\"];\n",
+                        room,
+                        n,
+                        d.to_string()
+                    ));
                 }
             }
-            if pending_commands.is_empty() {
-                break;
+        }
+        result.push_str("}\n");
+        result
+    }
+
+    #[cfg(feature = "json")]
+    fn to_json(&self) -> String {
+        let mut rooms = self.navigation.keys().collect::<Vec<_>>();
+        rooms.sort();
+        let rooms: Vec<Value> = rooms
+            .into_iter()
+            .map(|room| {
+                let doors: serde_json::Map<String, Value> = self.navigation[room]
+                    .iter()
+                    .map(|(d, n)| {
+                        let n = if n.is_empty() { Value::Null } else { json!(n) };
+                        (d.to_string(), n)
+                    })
+                    .collect();
+                let items: Vec<Value> = self
+                    .items
+                    .get(room)
+                    .cloned()
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|item| {
+                        let hazard = match self.hazards.get(item) {
+                            Some(Some(hazard)) => json!(hazard.to_string()),
+                            _ => Value::Null,
+                        };
+                        json!({"name": item, "hazard": hazard})
+                    })
+                    .collect();
+                json!({"name": room, "doors": doors, "items": items})
+            })
+            .collect();
+        serde_json::to_string_pretty(&json!({"droid": self.current, "rooms": rooms})).unwrap()
+    }
+
+    #[cfg(not(feature = "json"))]
+    fn to_json(&self) -> String {
+        panic!("built without the json feature");
+    }
+
+    // Writes the map as DOT or JSON by extension, or else as the ASCII grid.
+    fn save(&self, filename: &str) {
+        let contents = if filename.ends_with(".dot") {
+            self.to_dot()
+        } else if filename.ends_with(".json") {
+            self.to_json()
+        } else {
+            self.to_grid()
+        };
+        fs::write(filename, contents).unwrap();
+    }
+
+    fn render(&self) -> String {
+        let mut rooms = self.navigation.keys().collect::<Vec<_>>();
+        rooms.sort();
+        let mut result = String::new();
+        for room in rooms {
+            let here = if *room == self.current {
+                " (droid)"
+            } else {
+                ""
+            };
+            result.push_str(&format!("{}{}\n", room, here));
+            let mut exits = self.navigation[room].iter().collect::<Vec<_>>();
+            exits.sort_by_key(|(d, _)| d.to_string());
+            for (d, n) in exits {
+                let n = if n.is_empty() { "?" } else { n.as_str() };
+                result.push_str(&format!("  {} -> {}\n", d.to_string(), n));
+            }
+            if let Some(items) = self.items.get(room).filter(|items| !items.is_empty()) {
+                result.push_str(&format!("  items: {}\n", items.join(", ")));
             }
         }
+        result
     }
-    // drop everything
-    let all_items = my_items.clone();
-    drop_items(&mut intcode, &my_items);
-    let items_list = (1..all_items.len() + 1)
-        .into_iter()
-        .flat_map(|i| {
-            all_items
-                .to_owned()
-                .into_iter()
-                .combinations(i)
-                .collect::<Vec<_>>()
-        })
-        .collect::<Vec<_>>();
-    let go = navigation[&"Security Checkpoint".to_string()]
+}
+
+// Whether the game stopped asking for commands after these events.
+fn ended(events: &[Event]) -> bool {
+    events
         .iter()
-        .find(|(_, v)| *v == &"Pressure-Sensitive Floor".to_string())
-        .unwrap()
-        .0
-        .to_command();
-    for items in items_list.into_iter() {
-        take_items(&mut intcode, &items);
-        let mut cmd = go.to_owned();
-        std::mem::swap(&mut intcode.input_buf, &mut cmd);
-        intcode.output_buf.clear();
-        intcode.run();
-        println!("{}", intcode.output_buf);
-        if intcode
-            .output_buf
-            .contains("Analysis complete! You may proceed.")
-        {
-            println!("Passed with items {:?}", items);
+        .any(|e| matches!(e, Event::Password(_) | Event::GameOver(_)))
+}
+
+// Sends a command to the game and shows the answer, keeping the map of the
+// ship up to date. An empty command just collects the opening text.
+fn send(intcode: &mut IntCode, ship: &mut Ship, command: &str) -> Vec<Event> {
+    if !command.is_empty() {
+        intcode.input_buf = format!("{}\n", command);
+    }
+    intcode.output_buf.clear();
+    if !intcode.run_for(STEP_BUDGET) {
+        println!("gave up waiting for the game after {} steps", STEP_BUDGET);
+    }
+    show(&intcode.output_buf);
+    let events = parse_response(&intcode.output_buf);
+    ship.update(command, &events);
+    events
+}
+
+fn goto(intcode: &mut IntCode, ship: &mut Ship, room: &str) {
+    for command in ship.path(room) {
+        if ended(&send(intcode, ship, &command)) {
+            return;
+        }
+    }
+}
+
+// Walks through every door of the ship, picking up all the items that are
+// safe to carry.
+fn collect(intcode: &mut IntCode, ship: &mut Ship) {
+    loop {
+        let here = ship.items.get(&ship.current).cloned().unwrap_or_default();
+        for item in here {
+            let known = ship.hazards.contains_key(&item);
+            match ship.hazard(intcode, &item) {
+                Some(hazard) if !known => println!("leaving the {} behind: {}", item, hazard),
+                Some(_) => (),
+                None => {
+                    send(intcode, ship, &format!("take {}", item));
+                }
+            }
+        }
+        let commands = match ship.unexplored() {
+            Some(commands) => commands,
+            None => return,
+        };
+        for command in commands {
+            if ended(&send(intcode, ship, &command)) {
+                return;
+            }
+        }
+    }
+}
+
+fn solve(intcode: &mut IntCode, ship: &mut Ship) {
+    let checkpoint = "Security Checkpoint";
+    goto(intcode, ship, checkpoint);
+    let door = ship
+        .navigation
+        .get(checkpoint)
+        .and_then(|exits| exits.iter().find(|(_, v)| *v == "Pressure-Sensitive Floor"))
+        .map(|(d, _)| *d);
+    let door = match door {
+        Some(door) if ship.current == checkpoint => door,
+        _ => {
+            println!("the pressure-sensitive floor has not been found yet");
+            return;
+        }
+    };
+    match solve_checkpoint(intcode, door) {
+        Some(solution) => {
+            show(&intcode.output_buf);
+            ship.update(&door.to_string(), &parse_response(&intcode.output_buf));
             println!(
-                "Password = {}",
-                intcode
-                    .output_buf
-                    .lines()
-                    .filter_map(|line| {
-                        line.split_whitespace()
-                            .filter_map(|word| word.parse::<u64>().ok())
-                            .next()
-                    })
-                    .next()
-                    .unwrap()
+                "Passed with items {:?} after {} attempts",
+                solution.items, solution.attempts
             );
-            return;
+            println!("Password = {}", solution.password);
         }
-        drop_items(&mut intcode, &items);
+        None => println!("no set of items gets past the checkpoint"),
     }
 }
 
-fn take_items(intcode: &mut IntCode, items: &Vec<String>) {
-    let mut pending_commands = items
-        .into_iter()
-        .map(|i| {
-            let mut next_command = String::from("take ");
-            next_command.push_str(&i);
-            next_command.push('\n');
-            next_command
-        })
-        .collect::<Vec<String>>();
+fn explore(intcode: &mut IntCode) -> Ship {
+    let mut ship = Ship::default();
+    send(intcode, &mut ship, "");
+    collect(intcode, &mut ship);
+    solve(intcode, &mut ship);
+    ship
+}
+
+// Lets a human play the game, with a few commands for the autopilot on top:
+//   :map              what has been discovered so far
+//   :export <file>    write the map as DOT, JSON or an ASCII grid
+//   :goto <room>      walk to a known room
+//   :collect          explore the ship and pick up every safe item
+//   :solve            get past the security checkpoint with what is carried
+//   :save [name]      remember the machine and map under a name
+//   :load [name]      go back to a remembered machine and map
+//   :history          list the commands entered so far; !<n> repeats one
+//   :quit
+fn shell(intcode: &mut IntCode) -> Ship {
+    let mut ship = Ship::default();
+    let mut snapshots = HashMap::new();
+    let mut history: Vec<String> = Vec::new();
+    let mut over = ended(&send(intcode, &mut ship, ""));
     loop {
-        if intcode.input_buf.is_empty() {
-            match pending_commands.pop() {
-                Some(mut cmd) => {
-                    std::mem::swap(&mut intcode.input_buf, &mut cmd);
-                    intcode.output_buf.clear();
+        let mut buffer = String::new();
+        match io::stdin().read_line(&mut buffer) {
+            Err(error) => panic!("Error: {}", error),
+            Ok(0) => break,
+            Ok(_) => (),
+        }
+        let mut line = buffer.trim().to_string();
+        if let Some(n) = line.strip_prefix('!') {
+            match n.parse::<usize>().ok().and_then(|n| history.get(n)) {
+                Some(previous) => line = previous.to_owned(),
+                None => {
+                    println!("no such command in the history");
+                    continue;
+                }
+            }
+        }
+        if line.is_empty() {
+            continue;
+        }
+        history.push(line.to_owned());
+        let (command, argument) = match line.split_once(' ') {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (line.as_str(), ""),
+        };
+        let name = if argument.is_empty() {
+            "default"
+        } else {
+            argument
+        };
+        match command {
+            ":map" => print!("{}{}", ship.to_grid(), ship.render()),
+            ":export" if argument.is_empty() => println!("export to which file?"),
+            ":export" => ship.save(argument),
+            ":history" => {
+                for (i, previous) in history.iter().enumerate() {
+                    println!("{:4}  {}", i, previous);
                 }
-                None => break,
             }
+            ":save" => {
+                snapshots.insert(name.to_string(), (intcode.clone(), ship.clone(), over));
+                println!("saved {}", name);
+            }
+            ":load" => match snapshots.get(name) {
+                Some((machine, map, ended)) => {
+                    *intcode = machine.clone();
+                    ship = map.clone();
+                    over = *ended;
+                    println!("loaded {}", name);
+                }
+                None => println!("no snapshot called {}", name),
+            },
+            ":quit" => break,
+            _ if over => println!("the game is over, :load a snapshot to go on"),
+            ":goto" if !ship.navigation.contains_key(argument) => {
+                println!("no room called {} has been found", argument)
+            }
+            ":goto" => goto(intcode, &mut ship, argument),
+            ":collect" => collect(intcode, &mut ship),
+            ":solve" => solve(intcode, &mut ship),
+            _ if command.starts_with(':') => println!("unknown command {}", command),
+            _ => over = ended(&send(intcode, &mut ship, &line)),
+        }
+        if !over && command.starts_with(':') {
+            over = intcode.output_buf.is_empty() || !intcode.output_buf.ends_with("Command?\n");
         }
-        intcode.run();
-        print!("{}", intcode.output_buf);
     }
+    ship
+}
+
+struct Solution {
+    password: u64,
+    items: Vec<String>,
+    attempts: usize,
 }
 
-fn drop_items(intcode: &mut IntCode, items: &Vec<String>) {
-    let mut pending_commands = items
+// Finds which of the items held by the droid standing at the checkpoint to
+// carry through `door` onto the pressure-sensitive floor. Every attempt drops
+// the rest on a copy of the machine, and sets heavier than one found too heavy
+// or lighter than one found too light are never tried. On success `intcode`
+// becomes the machine that made it through.
+fn solve_checkpoint(intcode: &mut IntCode, door: Direction) -> Option<Solution> {
+    let items = try_command(&mut intcode.clone(), "inv")?
         .into_iter()
-        .map(|i| {
-            let mut next_command = String::from("drop ");
-            next_command.push_str(&i);
-            next_command.push('\n');
-            next_command
-        })
-        .collect::<Vec<String>>();
-    loop {
-        if intcode.input_buf.is_empty() {
-            match pending_commands.pop() {
-                Some(mut cmd) => {
-                    std::mem::swap(&mut intcode.input_buf, &mut cmd);
-                    intcode.output_buf.clear();
+        .find_map(|event| match event {
+            Event::Inventory(items) => Some(items),
+            _ => None,
+        })?;
+    let mut sets = (0..1u64 << items.len()).collect::<Vec<_>>();
+    sets.sort_by_key(|set| set.count_ones());
+    let mut heavy: Vec<u64> = Vec::new();
+    let mut light: Vec<u64> = Vec::new();
+    let mut attempts = 0;
+    for set in sets {
+        if heavy.iter().any(|h| set & h == *h) || light.iter().any(|l| set & l == set) {
+            continue;
+        }
+        let mut trial = intcode.clone();
+        let mut carried = Vec::new();
+        for (i, item) in items.iter().enumerate() {
+            if set & (1 << i) == 0 {
+                try_command(&mut trial, &format!("drop {}", item))?;
+            } else {
+                carried.push(item.to_string());
+            }
+        }
+        attempts += 1;
+        for event in try_command(&mut trial, &door.to_string())? {
+            match event {
+                Event::Password(password) => {
+                    *intcode = trial;
+                    return Some(Solution {
+                        password,
+                        items: carried,
+                        attempts,
+                    });
                 }
-                None => break,
+                Event::Ejected { too_heavy: true } => heavy.push(set),
+                Event::Ejected { too_heavy: false } => light.push(set),
+                _ => (),
             }
         }
-        intcode.run();
-        print!("{}", intcode.output_buf);
     }
+    None
 }
 
 fn parse(input: &str) -> Vec<i64> {
@@ -361,12 +957,14 @@ fn decode(n: i64) -> (ParamMode, ParamMode, ParamMode, i64) {
     (a, b, c, de)
 }
 
+#[derive(Clone)]
 struct IntCode {
     memory: Vec<i64>,
     ptr: usize,
     base: i64,
     input_buf: String,
     output_buf: String,
+    transcript: String,
 }
 
 impl IntCode {
@@ -377,6 +975,7 @@ impl IntCode {
             base: 0,
             input_buf: String::new(),
             output_buf: String::new(),
+            transcript: String::new(),
         }
     }
 
@@ -449,13 +1048,14 @@ impl IntCode {
                 }
                 let input_val = ((&self.input_buf[0..1]).parse::<char>().unwrap() as u8) as i64;
                 self.set(i + 1, arg1_mode, input_val);
-                self.input_buf.remove(0);
+                self.transcript.push(self.input_buf.remove(0));
                 i + 2
             }
             4 => {
                 let output_val = self.get(i + 1, arg1_mode);
                 let output = ((output_val) as u8) as char;
                 self.output_buf.push(output);
+                self.transcript.push(output);
                 if self.output_buf.contains("Command?\n") {
                     op = 99;
                 }
@@ -504,12 +1104,115 @@ impl IntCode {
         op
     }
 
-    fn run(&mut self) {
-        while self.ptr < self.memory.len() {
-            let op = self.execute();
-            if op == 99 {
-                break;
+    // Runs until the game asks for a command or halts, giving up after
+    // `budget` instructions and returning false.
+    fn run_for(&mut self, budget: usize) -> bool {
+        for _ in 0..budget {
+            if self.ptr >= self.memory.len() || self.execute() == 99 {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn room() {
+        let output = "
+
+== Hull Breach ==
+You got in through a hole in the floor here. To keep your ship from also freezing, the hole has been sealed.
+
+Doors here lead:
+- north
+- east
+- south
+
+Items here:
+- mug
+
+Command?
+";
+        let room = Event::Room {
+            name: "Hull Breach".to_string(),
+            description: "You got in through a hole in the floor here. To keep your ship \
+                          from also freezing, the hole has been sealed."
+                .to_string(),
+            doors: vec![Direction::North, Direction::East, Direction::South],
+            items: strings(&["mug"]),
+        };
+        assert_eq!(parse_response(output), vec![room]);
+    }
+
+    #[test]
+    fn items() {
+        let took = parse_response("\nYou take the spool of cat6.\n\nCommand?\n");
+        assert_eq!(took, vec![Event::Took("spool of cat6".to_string())]);
+        let dropped = parse_response("\nYou drop the mug.\n\nCommand?\n");
+        assert_eq!(dropped, vec![Event::Dropped("mug".to_string())]);
+        let output = "\nItems in your inventory:\n- mug\n- spool of cat6\n\nCommand?\n";
+        let expected = Event::Inventory(strings(&["mug", "spool of cat6"]));
+        assert_eq!(parse_response(output), vec![expected]);
+        let empty = parse_response("\nYou aren't carrying any items.\n\nCommand?\n");
+        assert_eq!(empty, vec![Event::Inventory(Vec::new())]);
+    }
+
+    #[test]
+    fn ejected() {
+        let output = "
+
+== Pressure-Sensitive Floor ==
+Analyzing...
+
+Doors here lead:
+- west
+
+A loud, robotic voice says \"Alert! Droids on this ship are lighter than the detected value!\" and you are ejected back to the checkpoint.
+
+== Security Checkpoint ==
+In the next room, a pressure-sensitive floor will verify your identity.
+
+Doors here lead:
+- north
+- east
+
+Command?
+";
+        let events = parse_response(output);
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[1], Event::Ejected { too_heavy: true });
+        match &events[2] {
+            Event::Room { name, doors, .. } => {
+                assert_eq!(name, "Security Checkpoint");
+                assert_eq!(doors, &vec![Direction::North, Direction::East]);
             }
+            other => panic!("expected the checkpoint, got {}", other),
         }
+        let light = parse_response(&output.replace("lighter", "heavier"));
+        assert_eq!(light[1], Event::Ejected { too_heavy: false });
+    }
+
+    // the game does not ask for another command once it is over
+    #[test]
+    fn game_end() {
+        let output = "A loud, robotic voice says \"Analysis complete! You may proceed.\" and you enter the cockpit.
+Santa notices your small droid, looks puzzled for a moment, realizes what has happened, and radios your ship directly.
+\"Oh, hello! You should be able to get in by typing 424242 on the keypad at the main airlock.\"
+";
+        let events = parse_response(output);
+        assert_eq!(events.last(), Some(&Event::Password(424_242)));
+        let reason = "The molten lava is way too hot! You melt!";
+        let lava = parse_response(&format!("\nYou take the molten lava.\n\n{}\n\n", reason));
+        assert_eq!(lava[0], Event::Took("molten lava".to_string()));
+        assert_eq!(lava[1], Event::GameOver(reason.to_string()));
+        assert_eq!(lava.len(), 2);
     }
 }